@@ -1,7 +1,23 @@
+// #[program] expands Anchor's IDL instructions at the crate root, and they
+// still call the deprecated AccountInfo::realloc. No narrower scope reaches
+// that generated code; the instruction handlers turn the lint back on.
+#![allow(deprecated)]
+
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::compute_units::sol_remaining_compute_units;
+use anchor_lang::solana_program::log::sol_log_compute_units;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked,
+    load_instruction_at_checked,
+};
+use anchor_lang::system_program;
 
 declare_id!("CZohQsF3D3cDDTtJnMZi9WirsknWxWyBKgHiLg5b1T8E");
 
+#[warn(deprecated)]
 #[program]
 pub mod ouh {
     use super::*;
@@ -12,226 +28,4784 @@ pub mod ouh {
         airtime_fee_bps: u16,
         min_limit: u64,
         max_limit: u64,
+        env_id: u8,
     ) -> Result<()> {
+        // User and transaction PDAs are shared by every environment, so only
+        // the default config's admin may stand up another one
+        if env_id != 0 {
+            let authorized = ctx.accounts.default_config
+                .as_ref()
+                .is_some_and(|default_config| default_config.admin == ctx.accounts.admin.key());
+            if !authorized {
+                return Err(OuhError::Unauthorized.into());
+            }
+        }
+        
         let config = &mut ctx.accounts.config;
         config.admin = ctx.accounts.admin.key();
+        config.env_id = env_id;
         config.crypto_fee_bps = crypto_fee_bps;
         config.airtime_fee_bps = airtime_fee_bps;
         config.min_limit = min_limit;
         config.max_limit = max_limit;
         config.paused = false;
+        config.promo_min_limit = min_limit;
+        config.max_promo_transactions = 0;
+        config.debug_logging = false;
+        config.balance_enforced = false;
+        config.crypto_settler = ctx.accounts.admin.key();
+        config.airtime_settler = ctx.accounts.admin.key();
+        config.allowed_airtime_denominations = [0; MAX_AIRTIME_DENOMINATIONS];
+        config.new_tx_frozen = false;
+        config.total_users = 0;
+        config.max_users = 0;
+        config.tip_recipient = ctx.accounts.admin.key();
+        config.max_tip_ngn = 0;
+        config.total_tips = 0;
+        config.velocity_multiplier = 0;
+        config.velocity_min_history = 0;
+        config.free_tx_count = 0;
+        config.usdc_min_unit = 0;
+        config.restricted_tx_types = 0;
+        config.business_days = ALL_WEEKDAYS;
+        config.business_hours = None;
+        config.initialized_at = current_timestamp()?;
+        config.max_retries = 0;
+        config.registration_deposit = 0;
+        config.partner_program = Pubkey::default();
+        config.max_user_usdc_exposure = 0;
+        config.airtime_fee_free_below = 0;
+        config.limit_timelock = 0;
+        config.pending_max_limit = 0;
+        config.max_limit_unlock_at = 0;
+        config.ngn_symbol = *b"NGN\0";
+        config.ngn_decimals = 2;
+        config.usdc_decimals = 6;
+        config.backup_admin = Pubkey::default();
+        config.admin_timeout = 0;
+        config.admin_last_seen = current_timestamp()?;
+        config.bucket_size = 0;
+        config.usdc_mint = Pubkey::default();
+        config.min_volume_accrual = 0;
+        config.failure_rebate_bps = 0;
+        config.allow_suspended_withdrawal = false;
+        config.dual_sign_threshold = 0;
+        config.risk_approver = Pubkey::default();
+        config.approval_threshold = 0;
+        config.emit_crypto_events = true;
+        config.emit_airtime_events = true;
+        config.min_fee_ngn = 0;
+        config.max_fee_ngn = 0;
+        config.min_pin_change_interval = 0;
+        config.max_client_skew = DEFAULT_MAX_CLIENT_SKEW;
+        config.max_epoch_lag = 0;
+        config.max_disputes = 0;
+        config.dispute_window = 0;
+        config.limit_multiplier_bps = BPS_DENOMINATOR as u16;
+        config.risk_checks_enabled = false;
+        config.settled_count = 0;
+        config.total_settlement_seconds = 0;
+        config.preserve_on_close = false;
+        config.referrer_fee_share_bps = 0;
+        config.unique_wallets = false;
+        config.loyalty_thresholds = [0; LOYALTY_LEVELS];
+        config.loyalty_limit_step_bps = 0;
+        config.pricing_key = Pubkey::default();
+        config.max_fee_ratio_bps = 0;
+        config.min_settlement_delay = 0;
+        config.accrue_net = false;
+        config.max_amount_digits = 0;
+        config.fail_grace_period = 0;
+        config.config_version = CONFIG_VERSION;
+        
+        config.config_epoch = 0;
+        let timestamp = config.initialized_at;
+        ctx.accounts.config_snapshot.record(config, timestamp);
         Ok(())
     }
     
-    pub fn register_user(
-        ctx: Context<RegisterUser>,
-        phone_number: [u8; 14],
-        pin_hash: [u8; 32],
+    // Every run opens a new config epoch with its own snapshot, so past
+    // transactions can be checked against the fees live when they were made
+    pub fn update_config(
+        ctx: Context<UpdateFeeConfig>,
+        crypto_fee_bps: u16,
+        airtime_fee_bps: u16,
+        min_limit: u64,
+        max_limit: u64,
     ) -> Result<()> {
-        let user_account = &mut ctx.accounts.user_account;
-        user_account.phone_number = phone_number;
-        user_account.wallet = ctx.accounts.user.key();
-        user_account.pin_hash = pin_hash;
-        user_account.total_volume = 0;
-        user_account.registered_at = Clock::get()?.unix_timestamp;
-        user_account.status = UserStatus::Active;
+        ctx.accounts.require(ROLE_CAN_UPDATE_FEES)?;
+        
+        if crypto_fee_bps as u64 > BPS_DENOMINATOR
+            || airtime_fee_bps as u64 > BPS_DENOMINATOR
+            || min_limit > max_limit
+        {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        
+        let config = &mut ctx.accounts.config;
+        set_fee_terms(config, crypto_fee_bps, airtime_fee_bps, min_limit, max_limit)?;
+        
+        config.config_epoch = config.config_epoch
+            .checked_add(1)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        let timestamp = current_timestamp()?;
+        ctx.accounts.config_snapshot.record(config, timestamp);
         Ok(())
     }
     
-    pub fn create_transaction(
-        ctx: Context<CreateTransaction>,
-        tx_id: [u8; 16],
-        user_phone: [u8; 14],
-        tx_type: TransactionType,
-        amount_ngn: u64,
-        amount_usdc: Option<u64>,
-        fee: u64,
+    // update_config plus the fee bounds, rebate and multiplier settings in
+    // one step. Every invariant is checked against the new values together,
+    // so no mix of old and new settings is ever live. Admin only.
+    pub fn update_config_atomic(
+        ctx: Context<UpdateFeeConfig>,
+        new_config: ConfigParams,
     ) -> Result<()> {
-        let config = &ctx.accounts.config;
+        ctx.accounts.require_admin()?;
+        new_config.validate()?;
         
-        // Check if contract is paused
-        if config.paused {
-            return Err(OuhError::ContractPaused.into());
-        }
+        let config = &mut ctx.accounts.config;
+        set_fee_terms(
+            config,
+            new_config.crypto_fee_bps,
+            new_config.airtime_fee_bps,
+            new_config.min_limit,
+            new_config.max_limit,
+        )?;
+        config.min_fee_ngn = new_config.min_fee_ngn;
+        config.max_fee_ngn = new_config.max_fee_ngn;
+        config.failure_rebate_bps = new_config.failure_rebate_bps;
+        config.referrer_fee_share_bps = new_config.referrer_fee_share_bps;
+        config.limit_multiplier_bps = new_config.limit_multiplier_bps;
         
-        // Check transaction limits
-        if amount_ngn < config.min_limit || amount_ngn > config.max_limit {
-            return Err(OuhError::TransactionLimitOutOfBounds.into());
-        }
+        config.config_epoch = config.config_epoch
+            .checked_add(1)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        let timestamp = current_timestamp()?;
+        ctx.accounts.config_snapshot.record(config, timestamp);
+        Ok(())
+    }
+    
+    // Promotes a max_limit increase recorded by update_config once its
+    // timelock has passed
+    pub fn apply_limit_increase(
+        ctx: Context<DelegatedConfigUpdate>,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_UPDATE_FEES)?;
         
-        let user_account = &ctx.accounts.user_account;
-        if user_account.status != UserStatus::Active {
-            return Err(OuhError::UserSuspended.into());
+        let config = &mut ctx.accounts.config;
+        if config.pending_max_limit == 0 {
+            return Err(OuhError::NoPendingLimitIncrease.into());
+        }
+        if current_timestamp()? < config.max_limit_unlock_at {
+            return Err(OuhError::LimitIncreaseLocked.into());
         }
         
-        let transaction = &mut ctx.accounts.transaction_account;
-        transaction.tx_id = tx_id;
-        transaction.user_phone = user_phone;
-        transaction.tx_type = tx_type;
-        transaction.amount_ngn = amount_ngn;
-        transaction.amount_usdc = amount_usdc;
-        transaction.status = TransactionStatus::Pending;
-        transaction.timestamp = Clock::get()?.unix_timestamp;
-        transaction.fee = fee;
-        
+        config.max_limit = config.pending_max_limit;
+        config.pending_max_limit = 0;
+        config.max_limit_unlock_at = 0;
         Ok(())
     }
     
-    pub fn complete_transaction(
-        ctx: Context<CompleteTransaction>,
+    // Delay in seconds before a max_limit increase can be applied; zero
+    // applies increases immediately
+    pub fn set_limit_timelock(
+        ctx: Context<UpdateConfig>,
+        limit_timelock: i64,
     ) -> Result<()> {
-        let transaction = &mut ctx.accounts.transaction_account;
-        transaction.status = TransactionStatus::Completed;
+        ctx.accounts.touch_admin()?;
+        if limit_timelock < 0 {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.limit_timelock = limit_timelock;
+        Ok(())
+    }
+    
+    pub fn set_paused(
+        ctx: Context<DelegatedConfigUpdate>,
+        paused: bool,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_PAUSE)?;
+        ctx.accounts.config.paused = paused;
+        Ok(())
+    }
+    
+    // Roles are granted by the super-admin only. Granting again replaces
+    // the permission bits; revoking closes the role account.
+    pub fn grant_role(
+        ctx: Context<GrantRole>,
+        grantee: Pubkey,
+        permissions: u8,
+    ) -> Result<()> {
+        let role = &mut ctx.accounts.role;
+        role.grantee = grantee;
+        role.permissions = permissions;
+        Ok(())
+    }
+    
+    pub fn revoke_role(
+        _ctx: Context<RevokeRole>,
+    ) -> Result<()> {
+        Ok(())
+    }
+    
+    pub fn set_promo_config(
+        ctx: Context<UpdateConfig>,
+        promo_min_limit: u64,
+        max_promo_transactions: u8,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        let config = &mut ctx.accounts.config;
+        config.promo_min_limit = promo_min_limit;
+        config.max_promo_transactions = max_promo_transactions;
+        Ok(())
+    }
+    
+    pub fn set_debug_logging(
+        ctx: Context<UpdateConfig>,
+        enabled: bool,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.debug_logging = enabled;
+        Ok(())
+    }
+    
+    pub fn set_balance_enforced(
+        ctx: Context<UpdateConfig>,
+        enforced: bool,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.balance_enforced = enforced;
+        Ok(())
+    }
+    
+    pub fn set_settlers(
+        ctx: Context<UpdateConfig>,
+        crypto_settler: Pubkey,
+        airtime_settler: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        let config = &mut ctx.accounts.config;
+        config.crypto_settler = crypto_settler;
+        config.airtime_settler = airtime_settler;
+        Ok(())
+    }
+    
+    // Zero entries are unused slots; an all-zero list allows any amount
+    pub fn set_airtime_denominations(
+        ctx: Context<UpdateConfig>,
+        denominations: [u64; MAX_AIRTIME_DENOMINATIONS],
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.allowed_airtime_denominations = denominations;
+        Ok(())
+    }
+    
+    // Maintenance mode: blocks new transactions while in-flight ones can
+    // still be settled. Independent of `paused`.
+    pub fn set_new_tx_frozen(
+        ctx: Context<DelegatedConfigUpdate>,
+        frozen: bool,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_PAUSE)?;
+        ctx.accounts.config.new_tx_frozen = frozen;
+        Ok(())
+    }
+    
+    // Hard cap on registrations for a capped pilot; zero means unlimited
+    pub fn set_max_users(
+        ctx: Context<UpdateConfig>,
+        max_users: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.max_users = max_users;
+        Ok(())
+    }
+    
+    // Tips are disabled until a non-zero cap is set
+    pub fn set_tip_config(
+        ctx: Context<UpdateConfig>,
+        tip_recipient: Pubkey,
+        max_tip_ngn: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        let config = &mut ctx.accounts.config;
+        config.tip_recipient = tip_recipient;
+        config.max_tip_ngn = max_tip_ngn;
+        Ok(())
+    }
+    
+    // A zero multiplier disables the velocity check
+    pub fn set_velocity_config(
+        ctx: Context<UpdateConfig>,
+        velocity_multiplier: u16,
+        velocity_min_history: u8,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        let config = &mut ctx.accounts.config;
+        config.velocity_multiplier = velocity_multiplier;
+        config.velocity_min_history = velocity_min_history;
+        Ok(())
+    }
+    
+    // Number of fee-free transactions granted to each new registration
+    pub fn set_free_tx_count(
+        ctx: Context<UpdateConfig>,
+        free_tx_count: u8,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.free_tx_count = free_tx_count;
+        Ok(())
+    }
+    
+    // USDC amounts must be a multiple of this many base units; 0 disables
+    pub fn set_usdc_min_unit(
+        ctx: Context<UpdateConfig>,
+        usdc_min_unit: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.usdc_min_unit = usdc_min_unit;
+        Ok(())
+    }
+    
+    // Restricts the flagged transaction types to the given weekdays (bit 0 is
+    // Sunday) and, optionally, a UTC hour window
+    pub fn set_business_hours(
+        ctx: Context<UpdateConfig>,
+        restricted_tx_types: u8,
+        business_days: u8,
+        business_hours: Option<[u8; 2]>,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if let Some([start, end]) = business_hours {
+            if start > 23 || end > 23 {
+                return Err(OuhError::InvalidConfig.into());
+            }
+        }
         
-        // Update user's total volume
-        let user_account = &mut ctx.accounts.user_account;
-        user_account.total_volume = user_account.total_volume
-            .checked_add(transaction.amount_ngn)
-            .unwrap();
+        let config = &mut ctx.accounts.config;
+        config.restricted_tx_types = restricted_tx_types;
+        config.business_days = business_days;
+        config.business_hours = business_hours;
+        Ok(())
+    }
+    
+    // How many times a failed transaction may go back to Pending
+    pub fn set_max_retries(
+        ctx: Context<UpdateConfig>,
+        max_retries: u8,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.max_retries = max_retries;
+        Ok(())
+    }
+    
+    // Refundable lamports locked in each new user PDA; zero disables
+    pub fn set_registration_deposit(
+        ctx: Context<UpdateConfig>,
+        registration_deposit: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.registration_deposit = registration_deposit;
+        Ok(())
+    }
+    
+    // Program that must own external order accounts linked to transactions
+    pub fn set_partner_program(
+        ctx: Context<UpdateConfig>,
+        partner_program: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.partner_program = partner_program;
+        Ok(())
+    }
+    
+    // Cap on a user's cumulative settled USDC; zero means unlimited
+    pub fn set_max_user_usdc_exposure(
+        ctx: Context<UpdateConfig>,
+        max_user_usdc_exposure: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.max_user_usdc_exposure = max_user_usdc_exposure;
+        Ok(())
+    }
+    
+    // Airtime top-ups below this amount carry no fee; zero disables
+    pub fn set_airtime_fee_free_below(
+        ctx: Context<DelegatedConfigUpdate>,
+        airtime_fee_free_below: u64,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_UPDATE_FEES)?;
+        ctx.accounts.config.airtime_fee_free_below = airtime_fee_free_below;
+        Ok(())
+    }
+    
+    // How clients render amounts; the symbol is NUL-padded UTF-8
+    pub fn set_display_metadata(
+        ctx: Context<UpdateConfig>,
+        ngn_symbol: [u8; 4],
+        ngn_decimals: u8,
+        usdc_decimals: u8,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        let config = &mut ctx.accounts.config;
+        config.ngn_symbol = ngn_symbol;
+        config.ngn_decimals = ngn_decimals;
+        config.usdc_decimals = usdc_decimals;
+        Ok(())
+    }
+    
+    // Designates a key that can take over as admin once the admin has been
+    // inactive for admin_timeout seconds; zero disables the takeover
+    pub fn set_backup_admin(
+        ctx: Context<UpdateConfig>,
+        backup_admin: Pubkey,
+        admin_timeout: i64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if admin_timeout < 0 {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        let config = &mut ctx.accounts.config;
+        config.backup_admin = backup_admin;
+        config.admin_timeout = admin_timeout;
+        Ok(())
+    }
+    
+    // Explicit liveness signal for an admin with nothing else to change
+    pub fn admin_heartbeat(
+        ctx: Context<UpdateConfig>,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()
+    }
+    
+    // Promotes the backup to admin after the admin has gone quiet. The
+    // backup slot is cleared so the new admin has to name a fresh one.
+    pub fn claim_by_backup(
+        ctx: Context<ClaimByBackup>,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let now = current_timestamp()?;
+        if config.admin_timeout == 0
+            || safe_elapsed(now, config.admin_last_seen) <= config.admin_timeout
+        {
+            return Err(OuhError::AdminStillActive.into());
+        }
         
+        config.admin = ctx.accounts.backup_admin.key();
+        config.backup_admin = Pubkey::default();
+        config.admin_last_seen = now;
         Ok(())
     }
     
-    pub fn get_user_balance(
-        ctx: Context<GetUserBalance>,
-    ) -> Result<u64> {
-        let user_account = &ctx.accounts.user_account;
-        Ok(user_account.total_volume)
+    // Granularity of stored amounts for users who opt into bucketing
+    pub fn set_bucket_size(
+        ctx: Context<UpdateConfig>,
+        bucket_size: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.bucket_size = bucket_size;
+        Ok(())
     }
+    
+    pub fn set_usdc_mint(
+        ctx: Context<UpdateConfig>,
+        usdc_mint: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.usdc_mint = usdc_mint;
+        Ok(())
+    }
+    
+    // Completed transactions below this amount accrue to micro_volume
+    // instead of total_volume; zero disables
+    pub fn set_min_volume_accrual(
+        ctx: Context<UpdateConfig>,
+        min_volume_accrual: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.min_volume_accrual = min_volume_accrual;
+        Ok(())
+    }
+    
+    // Share of the fee returned to the user when a transaction fails
+    pub fn set_failure_rebate_bps(
+        ctx: Context<DelegatedConfigUpdate>,
+        failure_rebate_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_UPDATE_FEES)?;
+        if failure_rebate_bps as u64 > BPS_DENOMINATOR {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.failure_rebate_bps = failure_rebate_bps;
+        Ok(())
+    }
+    
+    pub fn set_allow_suspended_withdrawal(
+        ctx: Context<UpdateConfig>,
+        allowed: bool,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.allow_suspended_withdrawal = allowed;
+        Ok(())
+    }
+    
+    // Amount above which a user's guardian must co-sign; zero disables
+    pub fn set_dual_sign_threshold(
+        ctx: Context<UpdateConfig>,
+        dual_sign_threshold: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.dual_sign_threshold = dual_sign_threshold;
+        Ok(())
+    }
+    
+    // Transactions above the threshold wait for the risk engine's signed
+    // approval before they can be completed; zero disables
+    pub fn set_risk_approver(
+        ctx: Context<UpdateConfig>,
+        risk_approver: Pubkey,
+        approval_threshold: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        let config = &mut ctx.accounts.config;
+        config.risk_approver = risk_approver;
+        config.approval_threshold = approval_threshold;
+        Ok(())
+    }
+    
+    // Lets high-volume types skip lifecycle events to save compute and log
+    // space. Withdrawals and admin actions always emit.
+    pub fn set_event_emission(
+        ctx: Context<UpdateConfig>,
+        emit_crypto_events: bool,
+        emit_airtime_events: bool,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        let config = &mut ctx.accounts.config;
+        config.emit_crypto_events = emit_crypto_events;
+        config.emit_airtime_events = emit_airtime_events;
+        Ok(())
+    }
+    
+    // Bounds on the charged fee; a zero max leaves it uncapped. Waived and
+    // zero-bps fees stay free.
+    pub fn set_fee_bounds(
+        ctx: Context<DelegatedConfigUpdate>,
+        min_fee_ngn: u64,
+        max_fee_ngn: u64,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_UPDATE_FEES)?;
+        if max_fee_ngn > 0 && min_fee_ngn > max_fee_ngn {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        let config = &mut ctx.accounts.config;
+        config.min_fee_ngn = min_fee_ngn;
+        config.max_fee_ngn = max_fee_ngn;
+        Ok(())
+    }
+    
+    // Seconds a user must wait between PIN changes; zero disables
+    pub fn set_min_pin_change_interval(
+        ctx: Context<UpdateConfig>,
+        min_pin_change_interval: i64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if min_pin_change_interval < 0 {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.min_pin_change_interval = min_pin_change_interval;
+        Ok(())
+    }
+    
+    // How far a client-supplied timestamp may drift from the cluster clock
+    pub fn set_max_client_skew(
+        ctx: Context<UpdateConfig>,
+        max_client_skew: i64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if max_client_skew < 0 {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.max_client_skew = max_client_skew;
+        Ok(())
+    }
+    
+    // Config epochs a pending transaction may fall behind and still be
+    // completed under the terms it was created with; zero disables
+    pub fn set_max_epoch_lag(
+        ctx: Context<UpdateConfig>,
+        max_epoch_lag: u64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.max_epoch_lag = max_epoch_lag;
+        Ok(())
+    }
+    
+    // More than max_disputes disputes within dispute_window seconds suspends
+    // the user; zero max_disputes disables
+    pub fn set_dispute_limits(
+        ctx: Context<UpdateConfig>,
+        max_disputes: u8,
+        dispute_window: i64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if dispute_window < 0 {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        let config = &mut ctx.accounts.config;
+        config.max_disputes = max_disputes;
+        config.dispute_window = dispute_window;
+        Ok(())
+    }
+    
+    // Scales min_limit and max_limit at enforcement time; 10000 is 1x
+    pub fn set_limit_multiplier(
+        ctx: Context<UpdateConfig>,
+        limit_multiplier_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if limit_multiplier_bps == 0 {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.limit_multiplier_bps = limit_multiplier_bps;
+        Ok(())
+    }
+    
+    // When on, every create_transaction must carry a risk nonce signed by
+    // the risk_approver key
+    pub fn set_risk_checks_enabled(
+        ctx: Context<UpdateConfig>,
+        enabled: bool,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.risk_checks_enabled = enabled;
+        Ok(())
+    }
+    
+    // When on, close_user keeps a UserArchive so a returning user gets their
+    // lifetime volume and original registration date back
+    pub fn set_preserve_on_close(
+        ctx: Context<UpdateConfig>,
+        preserve_on_close: bool,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.preserve_on_close = preserve_on_close;
+        Ok(())
+    }
+    
+    // Share of each completed transaction's fee credited to the referrer of
+    // the user who paid it
+    pub fn set_referrer_fee_share_bps(
+        ctx: Context<UpdateConfig>,
+        referrer_fee_share_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if referrer_fee_share_bps as u64 > BPS_DENOMINATOR {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.referrer_fee_share_bps = referrer_fee_share_bps;
+        Ok(())
+    }
+    
+    // When on, registration claims a WalletIndex for the signing wallet so
+    // it can hold only one phone at a time
+    pub fn set_unique_wallets(
+        ctx: Context<UpdateConfig>,
+        unique_wallets: bool,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.unique_wallets = unique_wallets;
+        Ok(())
+    }
+    
+    // Lifetime volume needed for each loyalty level, ascending, with unused
+    // levels left at zero. Each level raises the user's max limit by
+    // limit_step_bps.
+    pub fn set_loyalty_tiers(
+        ctx: Context<UpdateConfig>,
+        thresholds: [u64; LOYALTY_LEVELS],
+        limit_step_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        let used = thresholds.iter().take_while(|&&threshold| threshold > 0).count();
+        if thresholds[used..].iter().any(|&threshold| threshold > 0)
+            || thresholds[..used].windows(2).any(|pair| pair[0] >= pair[1])
+        {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        let config = &mut ctx.accounts.config;
+        config.loyalty_thresholds = thresholds;
+        config.loyalty_limit_step_bps = limit_step_bps;
+        Ok(())
+    }
+    
+    // Key the pricing service signs rate quotes with; the default key turns
+    // create_transaction_with_quote off
+    pub fn set_pricing_key(
+        ctx: Context<UpdateConfig>,
+        pricing_key: Pubkey,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.pricing_key = pricing_key;
+        Ok(())
+    }
+    
+    // Ceiling on the fee as a share of the amount, on top of max_fee_ngn;
+    // zero disables
+    pub fn set_max_fee_ratio_bps(
+        ctx: Context<DelegatedConfigUpdate>,
+        max_fee_ratio_bps: u16,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_UPDATE_FEES)?;
+        if max_fee_ratio_bps as u64 > BPS_DENOMINATOR {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.max_fee_ratio_bps = max_fee_ratio_bps;
+        Ok(())
+    }
+    
+    // Minimum seconds a transaction sits in Pending before complete_transaction
+    // accepts it, giving fraud checks time to act. force_complete ignores it.
+    pub fn set_min_settlement_delay(
+        ctx: Context<UpdateConfig>,
+        min_settlement_delay: i64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if min_settlement_delay < 0 {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.min_settlement_delay = min_settlement_delay;
+        Ok(())
+    }
+    
+    // Whether completions accrue volume gross (amount_ngn, the default) or net
+    // of the fee. Applies to transactions completed after the change.
+    pub fn set_accrue_net(
+        ctx: Context<UpdateConfig>,
+        accrue_net: bool,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.accrue_net = accrue_net;
+        Ok(())
+    }
+    
+    // Most significant digits amount_ngn may carry, for providers that
+    // reject finer amounts. 1_500 has two; zero disables.
+    pub fn set_max_amount_digits(
+        ctx: Context<UpdateConfig>,
+        max_amount_digits: u8,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        ctx.accounts.config.max_amount_digits = max_amount_digits;
+        Ok(())
+    }
+    
+    // Seconds after fail_transaction during which complete_transaction can
+    // still settle the transaction, for transient provider errors. It doesn't
+    // use up a retry. Zero disables.
+    pub fn set_fail_grace_period(
+        ctx: Context<UpdateConfig>,
+        fail_grace_period: i64,
+    ) -> Result<()> {
+        ctx.accounts.touch_admin()?;
+        if fail_grace_period < 0 {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        ctx.accounts.config.fail_grace_period = fail_grace_period;
+        Ok(())
+    }
+    
+    // Brings a Config written by an older build up to the current layout.
+    // Fields are only ever appended, so the old bytes keep their meaning and
+    // everything past them reads as zero once the account is grown; fields
+    // that must not start at zero then get their defaults. The version stamp
+    // makes each migration run once.
+    pub fn migrate_config(
+        ctx: Context<MigrateConfig>,
+        _env_id: u8,
+    ) -> Result<()> {
+        let info = ctx.accounts.config.to_account_info();
+        {
+            // The old layout may not deserialize yet; the admin is always the
+            // first field after the discriminator
+            let data = info.try_borrow_data()?;
+            if data.len() < 8 + 32 || data[..8] != *Config::DISCRIMINATOR {
+                return Err(OuhError::InvalidConfig.into());
+            }
+            if data[8..40] != ctx.accounts.admin.key().to_bytes() {
+                return Err(OuhError::Unauthorized.into());
+            }
+        }
+        
+        if info.data_len() < Config::LEN {
+            let rent = Rent::get()?.minimum_balance(Config::LEN);
+            let shortfall = rent.saturating_sub(info.lamports());
+            if shortfall > 0 {
+                system_program::transfer(
+                    CpiContext::new(
+                        ctx.accounts.system_program.to_account_info(),
+                        system_program::Transfer {
+                            from: ctx.accounts.admin.to_account_info(),
+                            to: info.clone(),
+                        },
+                    ),
+                    shortfall,
+                )?;
+            }
+            info.resize(Config::LEN)?;
+        }
+        
+        let mut config = Config::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        if config.config_version >= CONFIG_VERSION {
+            return Err(OuhError::ConfigAlreadyMigrated.into());
+        }
+        config.apply_defaults_since(config.config_version, current_timestamp()?);
+        config.config_version = CONFIG_VERSION;
+        config.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        Ok(())
+    }
+    
+    pub fn register_user(
+        ctx: Context<RegisterUser>,
+        phone_number: [u8; 14],
+        pin_hash: [u8; 32],
+    ) -> Result<()> {
+        open_user(
+            &mut ctx.accounts.user_account,
+            &mut ctx.accounts.config,
+            &ctx.accounts.user,
+            &ctx.accounts.system_program,
+            phone_number,
+            pin_hash,
+        )?;
+        index_wallet(
+            &ctx.accounts.config,
+            ctx.accounts.wallet_index.as_deref_mut(),
+            ctx.accounts.user.key(),
+            phone_number,
+        )?;
+        
+        // A returning user picks up where their closed account left off;
+        // the archive is spent once restored
+        if let Some(archive) = ctx.accounts.user_archive.as_ref() {
+            let user_account = &mut ctx.accounts.user_account;
+            user_account.total_volume = archive.total_volume;
+            user_account.registered_at = archive.registered_at;
+            close_account(&archive.to_account_info(), &ctx.accounts.user.to_account_info())?;
+        }
+        Ok(())
+    }
+    
+    // Onboarding in one step: a failure in the transaction leaves no user
+    // account behind either. Runs the same checks as create_transaction,
+    // including the tx_id reservation; a brand-new user has no guardian or
+    // budget to consult. It carries no risk nonce, so it's refused while risk
+    // checks are on.
+    pub fn register_and_transact(
+        ctx: Context<RegisterAndTransact>,
+        phone_number: [u8; 14],
+        pin_hash: [u8; 32],
+        tx_id: [u8; 16],
+        tx_type: TransactionType,
+        amount_ngn: u64,
+        amount_usdc: Option<u64>,
+    ) -> Result<()> {
+        open_user(
+            &mut ctx.accounts.user_account,
+            &mut ctx.accounts.config,
+            &ctx.accounts.user,
+            &ctx.accounts.system_program,
+            phone_number,
+            pin_hash,
+        )?;
+        index_wallet(
+            &ctx.accounts.config,
+            ctx.accounts.wallet_index.as_deref_mut(),
+            ctx.accounts.user.key(),
+            phone_number,
+        )?;
+        
+        // The reservation is closed by the account constraints once this succeeds
+        let now = current_timestamp()?;
+        if now >= ctx.accounts.reservation.expires_at {
+            return Err(OuhError::ReservationExpired.into());
+        }
+        open_transaction(
+            &mut ctx.accounts.transaction_account,
+            &mut ctx.accounts.user_account,
+            &mut ctx.accounts.config,
+            NewTransaction {
+                tx_id,
+                user_phone: phone_number,
+                tx_type,
+                amount_ngn,
+                amount_usdc,
+                note_ciphertext: None,
+                tip_ngn: None,
+                parent_tx_id: None,
+                priority: 0,
+                client_timestamp: None,
+                external_ref: None,
+                risk_nonce: None,
+                quoted_rate: None,
+            },
+            now,
+        )
+    }
+    
+    // Closes the user PDA, returning rent and the registration deposit to
+    // the wallet. Any NGN balance must be withdrawn first.
+    pub fn close_user(
+        ctx: Context<CloseUser>,
+    ) -> Result<()> {
+        // Keeping a banned PDA alive keeps its phone number from re-registering
+        if ctx.accounts.user_account.status == UserStatus::Banned {
+            return Err(OuhError::UserBanned.into());
+        }
+        if ctx.accounts.user_account.available_balance > 0 {
+            return Err(OuhError::BalanceNotEmpty.into());
+        }
+        
+        let config = &mut ctx.accounts.config;
+        config.total_users = config.total_users.saturating_sub(1);
+        
+        if config.preserve_on_close {
+            let Some(archive) = ctx.accounts.user_archive.as_mut() else {
+                return Err(OuhError::UserArchiveRequired.into());
+            };
+            let user_account = &ctx.accounts.user_account;
+            archive.phone_hash = phone_hash(&user_account.phone_number);
+            archive.total_volume = user_account.total_volume;
+            archive.registered_at = user_account.registered_at;
+        }
+        Ok(())
+    }
+    
+    // Admin credits a user's spendable NGN balance, e.g. after a cash-in.
+    pub fn credit_balance(
+        ctx: Context<UpdateUser>,
+        amount: u64,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.available_balance = user_account.available_balance
+            .checked_add(amount)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        Ok(())
+    }
+    
+    pub fn debit_balance(
+        ctx: Context<UpdateUser>,
+        amount: u64,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.available_balance = user_account.available_balance
+            .checked_sub(amount)
+            .ok_or(OuhError::InsufficientBalance)?;
+        Ok(())
+    }
+    
+    pub fn suspend_user(
+        ctx: Context<DelegatedUserUpdate>,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_SUSPEND_USERS)?;
+        let user_account = &mut ctx.accounts.user_account;
+        if user_account.status == UserStatus::Banned {
+            return Err(OuhError::UserBanned.into());
+        }
+        user_account.status = UserStatus::Suspended;
+        Ok(())
+    }
+    
+    pub fn reactivate_user(
+        ctx: Context<DelegatedUserUpdate>,
+    ) -> Result<()> {
+        ctx.accounts.require(ROLE_CAN_SUSPEND_USERS)?;
+        let user_account = &mut ctx.accounts.user_account;
+        if user_account.status == UserStatus::Banned {
+            return Err(OuhError::UserBanned.into());
+        }
+        user_account.status = UserStatus::Active;
+        Ok(())
+    }
+    
+    // Permanent, e.g. after confirmed fraud. There is deliberately no unban;
+    // suspend and reactivate both refuse a banned user.
+    pub fn ban_user(
+        ctx: Context<UpdateUser>,
+    ) -> Result<()> {
+        ctx.accounts.user_account.status = UserStatus::Banned;
+        Ok(())
+    }
+    
+    // User-controlled; the notifier reads it from TransactionCompleted
+    pub fn set_notifications(
+        ctx: Context<UpdateOwnUser>,
+        enabled: bool,
+    ) -> Result<()> {
+        ctx.accounts.user_account.notifications_enabled = enabled;
+        Ok(())
+    }
+    
+    // Corrects a settlement credited to the wrong user. Admin only and
+    // logged via VolumeTransferred.
+    pub fn transfer_volume(
+        ctx: Context<TransferVolume>,
+        amount: u64,
+    ) -> Result<()> {
+        let from_user = &mut ctx.accounts.from_user;
+        from_user.total_volume = from_user.total_volume
+            .checked_sub(amount)
+            .ok_or(OuhError::VolumeUnderflow)?;
+        
+        let to_user = &mut ctx.accounts.to_user;
+        to_user.total_volume = to_user.total_volume
+            .checked_add(amount)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        
+        emit!(VolumeTransferred {
+            from_phone: ctx.accounts.from_user.phone_number,
+            to_phone: ctx.accounts.to_user.phone_number,
+            amount,
+            admin: ctx.accounts.admin.key(),
+            timestamp: current_timestamp()?,
+        });
+        Ok(())
+    }
+    
+    // Rebuilds the user's volume counters from their transactions, passed as
+    // remaining accounts. Only volume those transactions account for
+    // survives, so every one still on-chain must be passed. Admin only.
+    pub fn reconcile_user(
+        ctx: Context<ReconcileUser>,
+    ) -> Result<()> {
+        let user_account = &ctx.accounts.user_account;
+        let mut total_volume: u64 = 0;
+        let mut micro_volume: u64 = 0;
+        let mut total_usdc_volume: u64 = 0;
+        
+        for (i, info) in ctx.remaining_accounts.iter().enumerate() {
+            require_compute(RECONCILE_ITEM_COMPUTE_UNITS)?;
+            if ctx.remaining_accounts[..i].iter().any(|seen| seen.key == info.key) {
+                return Err(OuhError::DuplicateAccount.into());
+            }
+            let transaction = load_transaction(info)?;
+            if transaction.user_phone != user_account.phone_number {
+                return Err(OuhError::Unauthorized.into());
+            }
+            // Disputed transactions keep their volume until resolved
+            if !matches!(
+                transaction.status,
+                TransactionStatus::Completed | TransactionStatus::Disputed
+            ) {
+                continue;
+            }
+            
+            // Counted as settlement credited it, less any refunds
+            let counted = transaction.accrued_volume;
+            if transaction.accrued_to_micro {
+                micro_volume = micro_volume
+                    .checked_add(counted)
+                    .ok_or(OuhError::ArithmeticOverflow)?;
+            } else {
+                total_volume = total_volume
+                    .checked_add(counted)
+                    .ok_or(OuhError::ArithmeticOverflow)?;
+            }
+            if let Some(amount_usdc) = transaction.amount_usdc {
+                let kept = amount_usdc
+                    .checked_sub(transaction.refunded_usdc())
+                    .ok_or(OuhError::ArithmeticOverflow)?;
+                total_usdc_volume = total_usdc_volume
+                    .checked_add(kept)
+                    .ok_or(OuhError::ArithmeticOverflow)?;
+            }
+        }
+        
+        let user_account = &mut ctx.accounts.user_account;
+        emit!(UserReconciled {
+            phone: user_account.phone_number,
+            volume_delta: signed_delta(user_account.total_volume, total_volume),
+            micro_volume_delta: signed_delta(user_account.micro_volume, micro_volume),
+            usdc_volume_delta: signed_delta(user_account.total_usdc_volume, total_usdc_volume),
+            admin: ctx.accounts.admin.key(),
+            timestamp: current_timestamp()?,
+        });
+        user_account.total_volume = total_volume;
+        user_account.micro_volume = micro_volume;
+        user_account.total_usdc_volume = total_usdc_volume;
+        Ok(())
+    }
+    
+    // Opt-in privacy mode: amounts are stored rounded to config.bucket_size.
+    // The fee, and any balance debit, still use the exact amount, so they
+    // narrow the bucket back down for anyone who looks.
+    pub fn set_bucketed(
+        ctx: Context<UpdateOwnUser>,
+        enabled: bool,
+    ) -> Result<()> {
+        ctx.accounts.user_account.bucketed = enabled;
+        Ok(())
+    }
+    
+    // Caches the wallet's USDC associated token account, checked once here
+    // against the derivation so later flows can compare against it
+    pub fn link_token_account(
+        ctx: Context<LinkTokenAccount>,
+        token_account: Pubkey,
+    ) -> Result<()> {
+        let usdc_mint = ctx.accounts.config.usdc_mint;
+        if usdc_mint == Pubkey::default() {
+            return Err(OuhError::InvalidTokenAccount.into());
+        }
+        
+        let user_account = &mut ctx.accounts.user_account;
+        let (expected, _) = Pubkey::find_program_address(
+            &[
+                user_account.wallet.as_ref(),
+                TOKEN_PROGRAM_ID.as_ref(),
+                usdc_mint.as_ref(),
+            ],
+            &ASSOCIATED_TOKEN_PROGRAM_ID,
+        );
+        if token_account != expected {
+            return Err(OuhError::InvalidTokenAccount.into());
+        }
+        user_account.token_account = token_account;
+        Ok(())
+    }
+    
+    // The cooldown keeps someone with brief access from locking the owner
+    // out by changing the PIN again and again
+    pub fn change_pin(
+        ctx: Context<ChangePin>,
+        current_pin_hash: [u8; 32],
+        new_pin_hash: [u8; 32],
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        if current_pin_hash != user_account.pin_hash {
+            return Err(OuhError::InvalidPin.into());
+        }
+        
+        let now = current_timestamp()?;
+        let interval = ctx.accounts.config.min_pin_change_interval;
+        if safe_elapsed(now, user_account.pin_changed_at) < interval {
+            return Err(OuhError::PinChangeTooSoon.into());
+        }
+        
+        user_account.pin_hash = new_pin_hash;
+        user_account.pin_changed_at = now;
+        Ok(())
+    }
+    
+    // Cosmetic name any client can render; zero-padded UTF-8, stored trimmed.
+    // An all-zero name clears it.
+    pub fn set_display_name(
+        ctx: Context<UpdateOwnUser>,
+        name: [u8; DISPLAY_NAME_LEN],
+    ) -> Result<()> {
+        ctx.accounts.user_account.display_name = normalize_display_name(&name)?;
+        Ok(())
+    }
+    
+    // Names the user who referred this one. Set once; it can't be moved to
+    // another referrer later.
+    pub fn set_referrer(ctx: Context<SetReferrer>) -> Result<()> {
+        let referrer_phone = ctx.accounts.referrer_account.phone_number;
+        let user_account = &mut ctx.accounts.user_account;
+        if user_account.referrer_phone.is_some() {
+            return Err(OuhError::ReferrerAlreadySet.into());
+        }
+        if referrer_phone == user_account.phone_number {
+            return Err(OuhError::InvalidReferrer.into());
+        }
+        user_account.referrer_phone = Some(referrer_phone);
+        Ok(())
+    }
+    
+    // M-of-N guardians who can move the account to a new wallet if the
+    // owner loses theirs. Unused slots are the default key; a zero
+    // threshold turns recovery off. Changing the set voids pending approvals.
+    pub fn set_recovery_guardians(
+        ctx: Context<UpdateOwnUser>,
+        guardians: [Pubkey; MAX_RECOVERY_GUARDIANS],
+        recovery_threshold: u8,
+    ) -> Result<()> {
+        let named: Vec<&Pubkey> = guardians
+            .iter()
+            .filter(|&guardian| *guardian != Pubkey::default())
+            .collect();
+        let distinct = named
+            .iter()
+            .enumerate()
+            .all(|(i, guardian)| !named[..i].contains(guardian));
+        if !distinct || recovery_threshold as usize > named.len() {
+            return Err(OuhError::InvalidGuardians.into());
+        }
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.recovery_guardians = guardians;
+        user_account.recovery_threshold = recovery_threshold;
+        Ok(())
+    }
+    
+    // One guardian's vote to move the account to new_wallet. Votes for a
+    // different wallet, or from before the guardian set changed, are reset.
+    pub fn approve_recovery(
+        ctx: Context<ApproveRecovery>,
+        new_wallet: Pubkey,
+    ) -> Result<()> {
+        let user_account = &ctx.accounts.user_account;
+        let Some(index) = user_account
+            .recovery_guardians
+            .iter()
+            .position(|guardian| *guardian == ctx.accounts.guardian.key())
+        else {
+            return Err(OuhError::Unauthorized.into());
+        };
+        if user_account.recovery_threshold == 0 {
+            return Err(OuhError::InvalidGuardians.into());
+        }
+        
+        let guardian_set = user_account.guardian_set_hash();
+        let request = &mut ctx.accounts.recovery_request;
+        if request.new_wallet != new_wallet || request.guardian_set != guardian_set {
+            request.phone_number = user_account.phone_number;
+            request.new_wallet = new_wallet;
+            request.guardian_set = guardian_set;
+            request.approvals = 0;
+            request.created_at = current_timestamp()?;
+        }
+        request.approvals |= 1 << index;
+        Ok(())
+    }
+    
+    // Rotates the wallet once recovery_threshold guardians have approved.
+    // The new wallet signs, so a typo'd key can't take the account.
+    pub fn recover_via_guardians(
+        ctx: Context<RecoverViaGuardians>,
+        new_wallet: Pubkey,
+    ) -> Result<()> {
+        let request = &ctx.accounts.recovery_request;
+        let user_account = &mut ctx.accounts.user_account;
+        if request.new_wallet != new_wallet
+            || request.guardian_set != user_account.guardian_set_hash()
+        {
+            return Err(OuhError::RecoveryMismatch.into());
+        }
+        if user_account.recovery_threshold == 0
+            || request.approvals.count_ones() < user_account.recovery_threshold as u32
+        {
+            return Err(OuhError::RecoveryThresholdNotMet.into());
+        }
+        user_account.wallet = new_wallet;
+        let phone_number = user_account.phone_number;
+        
+        // The lost wallet's claim on this phone moves to the new one; its
+        // rent goes to the new wallet, since nobody holds the old key
+        let old_index = ctx.accounts.old_wallet_index.to_account_info();
+        if old_index.owner == &crate::ID && !old_index.data_is_empty() {
+            let index = WalletIndex::try_deserialize(&mut &old_index.try_borrow_data()?[..])?;
+            if index.phone_number == phone_number {
+                close_account(&old_index, &ctx.accounts.new_wallet.to_account_info())?;
+            }
+        }
+        index_wallet(
+            &ctx.accounts.config,
+            ctx.accounts.new_wallet_index.as_deref_mut(),
+            new_wallet,
+            phone_number,
+        )
+    }
+    
+    // Opt-in co-signer for large transactions; None removes it
+    pub fn set_guardian(
+        ctx: Context<UpdateOwnUser>,
+        guardian: Option<Pubkey>,
+    ) -> Result<()> {
+        ctx.accounts.user_account.guardian = guardian;
+        Ok(())
+    }
+    
+    // Self-imposed weekly cap for a spending category. Changing a cap keeps
+    // the week's spend; a zero limit removes the category.
+    pub fn set_category_limit(
+        ctx: Context<SetCategoryLimit>,
+        category: u8,
+        weekly_limit: u64,
+    ) -> Result<()> {
+        let budget = &mut ctx.accounts.user_budget;
+        budget.phone_number = ctx.accounts.user_account.phone_number;
+        
+        match budget.find(category) {
+            Some(index) if weekly_limit == 0 => {
+                budget.limits[index] = CategoryLimit::default();
+            }
+            Some(index) => budget.limits[index].weekly_limit = weekly_limit,
+            None if weekly_limit == 0 => {}
+            None => {
+                let index = budget.limits
+                    .iter()
+                    .position(|limit| limit.weekly_limit == 0)
+                    .ok_or(OuhError::CategorySlotsFull)?;
+                budget.limits[index] = CategoryLimit {
+                    category,
+                    weekly_limit,
+                    spent: 0,
+                    week_start: current_timestamp()?,
+                };
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn set_user_promo(
+        ctx: Context<UpdateUser>,
+        enabled: bool,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.promo_enabled = enabled;
+        user_account.promo_tx_count = 0;
+        Ok(())
+    }
+    
+    // Claims a tx_id ahead of create_transaction so another device can't
+    // take it first. An expired reservation can be claimed by anyone.
+    pub fn reserve_tx_id(
+        ctx: Context<ReserveTxId>,
+        tx_id: [u8; 16],
+    ) -> Result<()> {
+        let now = current_timestamp()?;
+        let previous = ctx.accounts.reservation.owner;
+        if previous != Pubkey::default() && now < ctx.accounts.reservation.expires_at {
+            return Err(OuhError::TxIdReserved.into());
+        }
+        
+        // Taking over someone else's expired reservation buys its account
+        // from them: the new holder repays the rent they put up
+        if previous != Pubkey::default() && previous != ctx.accounts.user.key() {
+            let previous_owner = ctx.accounts.previous_owner
+                .as_ref()
+                .ok_or(OuhError::PreviousOwnerRequired)?;
+            if previous_owner.key() != previous {
+                return Err(OuhError::Unauthorized.into());
+            }
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.user.to_account_info(),
+                        to: previous_owner.to_account_info(),
+                    },
+                ),
+                ctx.accounts.reservation.to_account_info().lamports(),
+            )?;
+        }
+        
+        let reservation = &mut ctx.accounts.reservation;
+        reservation.tx_id = tx_id;
+        reservation.owner = ctx.accounts.user.key();
+        reservation.expires_at = now
+            .checked_add(RESERVATION_TTL_SECONDS)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        Ok(())
+    }
+    
+    // Anyone may close an expired reservation; the rent goes back to the
+    // wallet that holds it. A consumed reservation is already closed.
+    pub fn reclaim_reservation(
+        ctx: Context<ReclaimReservation>,
+    ) -> Result<()> {
+        if current_timestamp()? < ctx.accounts.reservation.expires_at {
+            return Err(OuhError::ReservationStillActive.into());
+        }
+        Ok(())
+    }
+    
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_transaction(
+        ctx: Context<CreateTransaction>,
+        tx_id: [u8; 16],
+        user_phone: [u8; 14],
+        tx_type: TransactionType,
+        amount_ngn: u64,
+        amount_usdc: Option<u64>,
+        note_ciphertext: Option<[u8; NOTE_CIPHERTEXT_LEN]>,
+        tip_ngn: Option<u64>,
+        parent_tx_id: Option<[u8; 16]>,
+        priority: u8,
+        category: Option<u8>,
+        client_timestamp: Option<i64>,
+        risk_nonce: Option<[u8; 16]>,
+        risk_signature: Option<[u8; 64]>,
+    ) -> Result<()> {
+        if ctx.accounts.config.debug_logging {
+            sol_log_compute_units();
+        }
+        
+        let now = current_timestamp()?;
+        ctx.accounts.open(
+            NewTransaction {
+                tx_id,
+                user_phone,
+                tx_type,
+                amount_ngn,
+                amount_usdc,
+                note_ciphertext,
+                tip_ngn,
+                parent_tx_id,
+                priority,
+                client_timestamp,
+                external_ref: None,
+                risk_nonce,
+                quoted_rate: None,
+            },
+            category,
+            risk_signature,
+            now,
+        )?;
+        
+        if ctx.accounts.config.debug_logging {
+            sol_log_compute_units();
+        }
+        Ok(())
+    }
+    
+    // Crypto purchase at a rate quoted by the pricing service, which signs
+    // the Borsh-encoded quote. The USDC amount is derived from the quote and
+    // the rate is kept on the transaction. With risk checks on, the quote's
+    // Ed25519 check goes before the risk engine's.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_transaction_with_quote(
+        ctx: Context<CreateTransaction>,
+        tx_id: [u8; 16],
+        user_phone: [u8; 14],
+        amount_ngn: u64,
+        quote: PriceQuote,
+        sig: [u8; 64],
+        risk_nonce: Option<[u8; 16]>,
+        risk_signature: Option<[u8; 64]>,
+    ) -> Result<()> {
+        let now = current_timestamp()?;
+        if now >= quote.expires_at {
+            return Err(OuhError::QuoteExpired.into());
+        }
+        let config = &ctx.accounts.config;
+        let Some(instructions) = ctx.accounts.instructions.as_ref() else {
+            return Err(OuhError::InvalidSignature.into());
+        };
+        if config.pricing_key == Pubkey::default() {
+            return Err(OuhError::InvalidSignature.into());
+        }
+        let slot = if config.risk_checks_enabled {
+            QUOTE_SIGNATURE_SLOT
+        } else {
+            RISK_SIGNATURE_SLOT
+        };
+        require_ed25519_signature(
+            instructions,
+            slot,
+            &config.pricing_key,
+            &quote.try_to_vec()?,
+            &sig,
+        )?;
+        let amount_usdc = quote.usdc_for(amount_ngn, config.usdc_decimals)?;
+        
+        ctx.accounts.open(
+            NewTransaction {
+                tx_id,
+                user_phone,
+                tx_type: TransactionType::Crypto,
+                amount_ngn,
+                amount_usdc: Some(amount_usdc),
+                note_ciphertext: None,
+                tip_ngn: None,
+                parent_tx_id: None,
+                priority: 0,
+                client_timestamp: None,
+                external_ref: None,
+                risk_nonce,
+                quoted_rate: Some(quote.rate),
+            },
+            None,
+            risk_signature,
+            now,
+        )
+    }
+    
+    // Dry run of create_transaction's checks against current state. Nothing
+    // is written; the failing check comes back as an error code.
+    pub fn simulate_transaction(
+        ctx: Context<SimulateTransaction>,
+        tx_type: TransactionType,
+        amount_ngn: u64,
+        amount_usdc: Option<u64>,
+        tip_ngn: Option<u64>,
+        priority: u8,
+        client_timestamp: Option<i64>,
+    ) -> Result<SimulationResult> {
+        let config = &ctx.accounts.config;
+        let user_account = &ctx.accounts.user_account;
+        let now = current_timestamp()?;
+        let terms = TransactionTerms {
+            tx_type,
+            amount_ngn,
+            amount_usdc,
+            tip_ngn: tip_ngn.unwrap_or(0),
+            priority,
+            client_timestamp,
+        };
+        
+        let outcome = validate_transaction(config, user_account, &terms, now)
+            .and_then(|quote| {
+                let total = amount_ngn
+                    .checked_add(quote.fee)
+                    .and_then(|total| total.checked_add(terms.tip_ngn))
+                    .ok_or(OuhError::ArithmeticOverflow)?;
+                let remaining_balance = if config.holds_balance(&terms.tx_type) {
+                    user_account.available_balance
+                        .checked_sub(total)
+                        .ok_or(OuhError::InsufficientBalance)?
+                } else {
+                    user_account.available_balance
+                };
+                Ok((quote.fee, remaining_balance))
+            });
+        
+        Ok(match outcome {
+            Ok((fee, remaining_balance)) => SimulationResult {
+                would_succeed: true,
+                reason_code: 0,
+                fee,
+                remaining_balance,
+            },
+            Err(error) => SimulationResult {
+                would_succeed: false,
+                reason_code: error.into(),
+                fee: 0,
+                remaining_balance: user_account.available_balance,
+            },
+        })
+    }
+    
+    pub fn complete_transaction(
+        ctx: Context<CompleteTransaction>,
+    ) -> Result<()> {
+        let debug_logging = ctx.accounts.config.debug_logging;
+        if debug_logging {
+            sol_log_compute_units();
+        }
+        
+        ctx.accounts.require_settler()?;
+        let now = current_timestamp()?;
+        let transaction = &ctx.accounts.transaction_account;
+        let in_grace = transaction.status == TransactionStatus::Failed
+            && now < transaction.fail_grace_until;
+        if transaction.status != TransactionStatus::Pending && !in_grace {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        if transaction.awaits_approval() {
+            return Err(OuhError::ApprovalRequired.into());
+        }
+        // Terms from too many config updates ago are no longer honored
+        let config = &ctx.accounts.config;
+        let lag = config.config_epoch.saturating_sub(transaction.config_epoch);
+        if config.max_epoch_lag > 0 && lag > config.max_epoch_lag {
+            return Err(OuhError::EpochExpired.into());
+        }
+        
+        // Failure released the held funds; they're taken again as a retry
+        // would
+        if in_grace {
+            retake_transaction_funds(
+                &mut ctx.accounts.transaction_account,
+                &mut ctx.accounts.user_account,
+                &mut ctx.accounts.config,
+            )?;
+        }
+        
+        let config = &ctx.accounts.config;
+        let elapsed = safe_elapsed(now, ctx.accounts.transaction_account.timestamp);
+        if elapsed < config.min_settlement_delay {
+            return Err(OuhError::SettlementTooSoon.into());
+        }
+        
+        // Feeds estimate_settlement_time. Force-completions are left out;
+        // they're stuck transactions, not typical latency.
+        let elapsed = elapsed as u64;
+        let config = &mut ctx.accounts.config;
+        config.settled_count = config.settled_count
+            .checked_add(1)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        config.total_settlement_seconds = config.total_settlement_seconds.saturating_add(elapsed);
+        
+        record_daily_stats(
+            &mut ctx.accounts.daily_stats,
+            ctx.accounts.transaction_account.amount_ngn,
+            now,
+        )?;
+        
+        settle_transaction(
+            &mut ctx.accounts.transaction_account,
+            &mut ctx.accounts.user_account,
+            &ctx.accounts.config,
+        )?;
+        
+        credit_referrer(
+            ctx.accounts.referrer_account.as_deref(),
+            &ctx.accounts.user_account,
+            ctx.accounts.transaction_account.fee,
+            &ctx.accounts.config,
+        )?;
+        
+        if debug_logging {
+            sol_log_compute_units();
+        }
+        Ok(())
+    }
+    
+    // Records the risk engine's sign-off on a flagged transaction. Anyone may
+    // submit it; the Ed25519 check over tx_id || nonce is what authorizes it.
+    pub fn provide_approval(
+        ctx: Context<ProvideApproval>,
+        nonce: [u8; 32],
+        sig: [u8; 64],
+    ) -> Result<()> {
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Pending || !transaction.awaits_approval() {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        if nonce != transaction.approval_nonce {
+            return Err(OuhError::InvalidSignature.into());
+        }
+        
+        let message = [transaction.tx_id.as_ref(), nonce.as_ref()].concat();
+        require_ed25519_signature(
+            &ctx.accounts.instructions,
+            RISK_SIGNATURE_SLOT,
+            &ctx.accounts.config.risk_approver,
+            &message,
+            &sig,
+        )?;
+        transaction.approved = true;
+        
+        if ctx.accounts.config.emits_events_for(&transaction.tx_type) {
+            emit!(TransactionApproved {
+                tx_id: transaction.tx_id,
+                approver: ctx.accounts.config.risk_approver,
+                timestamp: current_timestamp()?,
+            });
+        }
+        Ok(())
+    }
+    
+    // Settlement failed off-chain
+    pub fn fail_transaction(
+        ctx: Context<UpdateTransactionStatus>,
+    ) -> Result<()> {
+        ctx.accounts.require_settler()?;
+        if ctx.accounts.transaction_account.status != TransactionStatus::Pending {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        
+        fail_transaction_funds(
+            &mut ctx.accounts.transaction_account,
+            &mut ctx.accounts.user_account,
+            &mut ctx.accounts.config,
+        )?;
+        
+        let grace_period = ctx.accounts.config.fail_grace_period;
+        ctx.accounts.transaction_account.fail_grace_until = if grace_period > 0 {
+            current_timestamp()?
+                .checked_add(grace_period)
+                .ok_or(OuhError::ArithmeticOverflow)?
+        } else {
+            0
+        };
+        Ok(())
+    }
+    
+    // Parks a pending transaction for manual review. Held transactions can't
+    // be completed or cancelled until released.
+    pub fn hold_transaction(
+        ctx: Context<UpdateTransactionStatus>,
+    ) -> Result<()> {
+        ctx.accounts.require_settler()?;
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Pending {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        transaction.status = TransactionStatus::Held;
+        Ok(())
+    }
+    
+    // Review passed; the transaction settles normally from here
+    pub fn release_transaction(
+        ctx: Context<UpdateTransactionStatus>,
+    ) -> Result<()> {
+        ctx.accounts.require_settler()?;
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Held {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        transaction.status = TransactionStatus::Pending;
+        Ok(())
+    }
+    
+    // Review failed. Refunds as fail_transaction does.
+    pub fn reject_held(
+        ctx: Context<UpdateTransactionStatus>,
+    ) -> Result<()> {
+        ctx.accounts.require_settler()?;
+        if ctx.accounts.transaction_account.status != TransactionStatus::Held {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        
+        fail_transaction_funds(
+            &mut ctx.accounts.transaction_account,
+            &mut ctx.accounts.user_account,
+            &mut ctx.accounts.config,
+        )?;
+        Ok(())
+    }
+    
+    // Moves a failed transaction back to Pending, at most config.max_retries
+    // times. The amount and tip are held again as at creation.
+    pub fn retry_transaction(
+        ctx: Context<UpdateTransactionStatus>,
+    ) -> Result<()> {
+        ctx.accounts.require_settler()?;
+        let config = &mut ctx.accounts.config;
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Failed {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        if transaction.retry_count >= config.max_retries {
+            return Err(OuhError::RetryLimitReached.into());
+        }
+        
+        retake_transaction_funds(transaction, &mut ctx.accounts.user_account, config)?;
+        
+        transaction.retry_count = transaction.retry_count
+            .checked_add(1)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        transaction.status = TransactionStatus::Pending;
+        
+        if config.emits_events_for(&transaction.tx_type) {
+            emit!(TransactionRetried {
+                tx_id: transaction.tx_id,
+                retry_count: transaction.retry_count,
+                timestamp: current_timestamp()?,
+            });
+        }
+        Ok(())
+    }
+    
+    // Break-glass completion for a transaction stuck in Pending after the
+    // funds already moved off-chain. Admin only, and deliberately ignores
+    // the pause flag.
+    pub fn force_complete(
+        ctx: Context<ForceComplete>,
+        override_reason: u8,
+    ) -> Result<()> {
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Pending {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        
+        let now = current_timestamp()?;
+        record_daily_stats(&mut ctx.accounts.daily_stats, transaction.amount_ngn, now)?;
+        settle_transaction(transaction, &mut ctx.accounts.user_account, &ctx.accounts.config)?;
+        credit_referrer(
+            ctx.accounts.referrer_account.as_deref(),
+            &ctx.accounts.user_account,
+            transaction.fee,
+            &ctx.accounts.config,
+        )?;
+        
+        emit!(ForceCompleted {
+            tx_id: transaction.tx_id,
+            admin: ctx.accounts.admin.key(),
+            override_reason,
+            timestamp: now,
+        });
+        
+        Ok(())
+    }
+    
+    // User-initiated abort before settlement. Releases whatever was taken
+    // from the available balance and backs the tip out of the totals.
+    pub fn cancel_transaction(
+        ctx: Context<CancelTransaction>,
+    ) -> Result<()> {
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Pending {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        transaction.status = TransactionStatus::Cancelled;
+        
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.release_pending_usdc(transaction);
+        user_account.available_balance = user_account.available_balance
+            .checked_add(transaction.debited_amount)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        transaction.debited_amount = 0;
+        
+        let config = &mut ctx.accounts.config;
+        config.total_tips = config.total_tips.saturating_sub(transaction.tip_ngn);
+        Ok(())
+    }
+    
+    // The owner contests a completed transaction. A burst of disputes is a
+    // fraud signal, so too many inside the window suspends the user.
+    pub fn dispute_transaction(
+        ctx: Context<DisputeTransaction>,
+    ) -> Result<()> {
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Completed {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        transaction.status = TransactionStatus::Disputed;
+        
+        let now = current_timestamp()?;
+        let config = &ctx.accounts.config;
+        let user_account = &mut ctx.accounts.user_account;
+        if safe_elapsed(now, user_account.dispute_window_start) >= config.dispute_window {
+            user_account.recent_dispute_count = 0;
+            user_account.dispute_window_start = now;
+        }
+        user_account.recent_dispute_count = user_account.recent_dispute_count.saturating_add(1);
+        
+        let abusive = config.max_disputes > 0
+            && user_account.recent_dispute_count > config.max_disputes;
+        if abusive && user_account.status == UserStatus::Active {
+            user_account.status = UserStatus::Suspended;
+        }
+        
+        if config.emits_events_for(&transaction.tx_type) {
+            emit!(TransactionDisputed {
+                tx_id: transaction.tx_id,
+                user_phone: transaction.user_phone,
+                recent_dispute_count: user_account.recent_dispute_count,
+                suspended: user_account.status == UserStatus::Suspended,
+                timestamp: now,
+            });
+        }
+        Ok(())
+    }
+    
+    // Closes a dispute once it's been investigated off-chain; any refund is
+    // paid there too. An upheld dispute keeps counting towards the abuse
+    // limit; a rejected one no longer does.
+    pub fn resolve_dispute(
+        ctx: Context<ResolveDispute>,
+        upheld: bool,
+    ) -> Result<()> {
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Disputed {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        transaction.status = TransactionStatus::Completed;
+        
+        if !upheld {
+            let user_account = &mut ctx.accounts.user_account;
+            user_account.recent_dispute_count = user_account.recent_dispute_count.saturating_sub(1);
+        }
+        Ok(())
+    }
+    
+    // Reverses part of a completed transaction, e.g. when the provider only
+    // partly fulfilled it. The money moves off-chain; here the amount comes
+    // off the user's volume. Refunds add up to at most amount_ngn, and the
+    // transaction is Refunded once they reach it.
+    pub fn refund_transaction(
+        ctx: Context<UpdateTransactionStatus>,
+        refund_amount: u64,
+    ) -> Result<()> {
+        ctx.accounts.require_settler()?;
+        let transaction = &mut ctx.accounts.transaction_account;
+        if transaction.status != TransactionStatus::Completed {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        let refunded_amount = transaction.refunded_amount
+            .checked_add(refund_amount)
+            .filter(|&total| refund_amount > 0 && total <= transaction.amount_ngn)
+            .ok_or(OuhError::InvalidRefundAmount)?;
+        let refunded_usdc = transaction.refunded_usdc();
+        transaction.refunded_amount = refunded_amount;
+        if refunded_amount == transaction.amount_ngn {
+            transaction.status = TransactionStatus::Refunded;
+        }
+        
+        // Taken from the counter settlement credited. Under net accrual the
+        // fee part was never credited, so the cut stops at what was.
+        let cut = refund_amount.min(transaction.accrued_volume);
+        transaction.accrued_volume = transaction.accrued_volume
+            .checked_sub(cut)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        let user_account = &mut ctx.accounts.user_account;
+        let volume = if transaction.accrued_to_micro {
+            &mut user_account.micro_volume
+        } else {
+            &mut user_account.total_volume
+        };
+        *volume = volume.checked_sub(cut).ok_or(OuhError::VolumeUnderflow)?;
+        
+        // The USDC leg comes off in proportion
+        let usdc_cut = transaction.refunded_usdc()
+            .checked_sub(refunded_usdc)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        user_account.total_usdc_volume = user_account.total_usdc_volume
+            .checked_sub(usdc_cut)
+            .ok_or(OuhError::VolumeUnderflow)?;
+        Ok(())
+    }
+    
+    // Archiving hides a settled transaction from active views while keeping
+    // the record on-chain for audits.
+    pub fn archive_transaction(
+        ctx: Context<ArchiveTransaction>,
+    ) -> Result<()> {
+        let transaction = &mut ctx.accounts.transaction_account;
+        if !transaction.is_settled() {
+            return Err(OuhError::InvalidTransactionStatus.into());
+        }
+        transaction.archived = true;
+        Ok(())
+    }
+    
+    pub fn unarchive_transaction(
+        ctx: Context<ArchiveTransaction>,
+    ) -> Result<()> {
+        ctx.accounts.transaction_account.archived = false;
+        Ok(())
+    }
+    
+    // Closes settled transactions passed as remaining accounts, returning
+    // their rent to the user. Pending ones are skipped.
+    pub fn close_transactions_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, CloseTransactionsBatch<'info>>,
+    ) -> Result<u8> {
+        if ctx.remaining_accounts.len() > MAX_CLOSE_BATCH {
+            return Err(OuhError::BatchTooLarge.into());
+        }
+        
+        let user_account = &ctx.accounts.user_account;
+        let user = ctx.accounts.user.to_account_info();
+        let mut closed: u8 = 0;
+        for info in ctx.remaining_accounts.iter() {
+            require_compute(CLOSE_BATCH_ITEM_COMPUTE_UNITS)?;
+            let transaction = load_transaction(info)?;
+            if transaction.user_phone != user_account.phone_number {
+                return Err(OuhError::Unauthorized.into());
+            }
+            if !transaction.is_settled() {
+                continue;
+            }
+            close_account(info, &user)?;
+            closed = closed.checked_add(1).ok_or(OuhError::ArithmeticOverflow)?;
+        }
+        
+        Ok(closed)
+    }
+    
+    // Walks a transaction's ancestors, passed in order via remaining_accounts,
+    // and returns the ids from the transaction up to its root
+    pub fn get_transaction_chain(
+        ctx: Context<GetTransactionChain>,
+    ) -> Result<Vec<[u8; 16]>> {
+        let mut current = (*ctx.accounts.transaction_account).clone();
+        let mut chain = vec![current.tx_id];
+        
+        for info in ctx.remaining_accounts.iter().take(MAX_CHAIN_DEPTH) {
+            let Some(parent_tx_id) = current.parent_tx_id else {
+                break;
+            };
+            let parent = load_transaction(info)?;
+            if parent.tx_id != parent_tx_id {
+                return Err(OuhError::InvalidParentTransaction.into());
+            }
+            chain.push(parent.tx_id);
+            current = parent;
+        }
+        
+        Ok(chain)
+    }
+    
+    // Summaries of the user's transactions with user_seq in
+    // [from_seq, from_seq + limit). Candidate PDAs come in remaining accounts;
+    // ones outside the range, and archived ones unless asked for, are skipped.
+    pub fn get_user_tx_page(
+        ctx: Context<GetUserTxPage>,
+        from_seq: u64,
+        limit: u8,
+        include_archived: bool,
+    ) -> Result<Vec<TransactionSummary>> {
+        let user_account = &ctx.accounts.user_account;
+        let limit = (limit as usize).min(MAX_PAGE_SIZE);
+        let end_seq = from_seq.saturating_add(limit as u64);
+        
+        let mut page = Vec::with_capacity(limit);
+        for info in ctx.remaining_accounts.iter() {
+            if page.len() >= limit {
+                break;
+            }
+            let transaction = load_transaction(info)?;
+            if transaction.user_phone != user_account.phone_number {
+                return Err(OuhError::Unauthorized.into());
+            }
+            if transaction.user_seq < from_seq || transaction.user_seq >= end_seq {
+                continue;
+            }
+            if transaction.archived && !include_archived {
+                continue;
+            }
+            page.push(TransactionSummary {
+                tx_id: transaction.tx_id,
+                user_seq: transaction.user_seq,
+                tx_type: transaction.tx_type,
+                status: transaction.status,
+                amount_ngn: transaction.amount_ngn,
+                timestamp: transaction.timestamp,
+                archived: transaction.archived,
+            });
+        }
+        
+        Ok(page)
+    }
+    
+    pub fn get_config(
+        ctx: Context<ReadConfig>,
+    ) -> Result<Config> {
+        Ok((*ctx.accounts.config).clone())
+    }
+    
+    // Lets a client check a tx_id before create_transaction. The transaction
+    // PDA is passed as the first remaining account since it may not exist.
+    pub fn is_tx_id_used(
+        ctx: Context<CheckTxId>,
+        tx_id: [u8; 16],
+    ) -> Result<bool> {
+        let Some(account) = ctx.remaining_accounts.first() else {
+            return Err(OuhError::InvalidTransactionAccount.into());
+        };
+        let (expected, _) =
+            Pubkey::find_program_address(&[TRANSACTION_SEED, &tx_id], &crate::ID);
+        if account.key() != expected {
+            return Err(OuhError::InvalidTransactionAccount.into());
+        }
+        
+        Ok(*account.owner == crate::ID && !account.data_is_empty())
+    }
+    
+    // Mean creation-to-completion latency over every complete_transaction:
+    // total_settlement_seconds / settled_count. Zero until something settles.
+    pub fn estimate_settlement_time(
+        ctx: Context<ReadConfig>,
+    ) -> Result<i64> {
+        let config = &ctx.accounts.config;
+        if config.settled_count == 0 {
+            return Ok(0);
+        }
+        let mean = config.total_settlement_seconds / config.settled_count;
+        Ok(i64::try_from(mean).unwrap_or(i64::MAX))
+    }
+    
+    // Seconds since initialize_config
+    pub fn get_uptime(
+        ctx: Context<ReadConfig>,
+    ) -> Result<i64> {
+        let now = current_timestamp()?;
+        Ok(safe_elapsed(now, ctx.accounts.config.initialized_at))
+    }
+    
+    // Point-in-time claim of a user's volume. PDAs can't sign, so the digest
+    // binds the fields to this program and config; the claim is trusted
+    // because it comes back as this program's return data.
+    pub fn attest_volume(
+        ctx: Context<AttestVolume>,
+    ) -> Result<VolumeAttestation> {
+        let user_account = &ctx.accounts.user_account;
+        let timestamp = current_timestamp()?;
+        let digest = hashv(&[
+            crate::ID.as_ref(),
+            ctx.accounts.config.key().as_ref(),
+            &user_account.phone_number,
+            &user_account.total_volume.to_le_bytes(),
+            &timestamp.to_le_bytes(),
+        ]);
+        
+        Ok(VolumeAttestation {
+            phone: user_account.phone_number,
+            total_volume: user_account.total_volume,
+            timestamp,
+            digest: digest.to_bytes(),
+        })
+    }
+    
+    // Completed-transaction totals for a UTC day (unix time / 86400)
+    pub fn get_daily_stats(
+        ctx: Context<GetDailyStats>,
+        _day: u32,
+    ) -> Result<DailyStats> {
+        Ok((*ctx.accounts.daily_stats).clone())
+    }
+    
+    pub fn get_user_balance(
+        ctx: Context<GetUserBalance>,
+    ) -> Result<u64> {
+        let user_account = &ctx.accounts.user_account;
+        Ok(user_account.total_volume)
+    }
+    
+    pub fn get_user_stats(
+        ctx: Context<GetUserStats>,
+    ) -> Result<UserStats> {
+        let user_account = &ctx.accounts.user_account;
+        Ok(UserStats {
+            phone: user_account.phone_number,
+            total_volume: user_account.total_volume,
+            tx_count: user_account.tx_count,
+            loyalty_level: user_account.loyalty_level,
+            tx_chain_hash: user_account.tx_chain_hash,
+        })
+    }
+    
+    // Advisory ComputeBudget limit for a create/settle of this shape
+    pub fn get_recommended_compute_units(
+        _ctx: Context<RecommendComputeUnits>,
+        tx_type: TransactionType,
+        amount_ngn: u64,
+    ) -> Result<u32> {
+        Ok(recommended_compute_units(tx_type, amount_ngn))
+    }
+    
+    // get_user_balance for a group of users passed as remaining accounts
+    pub fn get_balances_batch(
+        ctx: Context<GetBalancesBatch>,
+    ) -> Result<Vec<UserBalanceEntry>> {
+        if ctx.remaining_accounts.len() > MAX_BALANCE_BATCH {
+            return Err(OuhError::BatchTooLarge.into());
+        }
+        
+        ctx.remaining_accounts
+            .iter()
+            .map(|info| {
+                require_compute(BALANCE_BATCH_ITEM_COMPUTE_UNITS)?;
+                let user_account = load_user(info)?;
+                Ok(UserBalanceEntry {
+                    phone: user_account.phone_number,
+                    total_volume: user_account.total_volume,
+                    status: user_account.status,
+                })
+            })
+            .collect()
+    }
+}
+
+// PDA Seeds
+pub const USER_SEED: &[u8] = b"user";
+pub const TRANSACTION_SEED: &[u8] = b"transaction";
+pub const CONFIG_SEED: &[u8] = b"config";
+pub const RESERVATION_SEED: &[u8] = b"reservation";
+pub const ROLE_SEED: &[u8] = b"role";
+pub const CONFIG_SNAPSHOT_SEED: &[u8] = b"config_snapshot";
+pub const BUDGET_SEED: &[u8] = b"budget";
+pub const ARCHIVE_SEED: &[u8] = b"archive";
+pub const WALLET_SEED: &[u8] = b"wallet";
+pub const DAILY_STATS_SEED: &[u8] = b"daily_stats";
+pub const RECOVERY_SEED: &[u8] = b"recovery";
+
+// Role permission bits
+pub const ROLE_CAN_PAUSE: u8 = 1 << 0;
+pub const ROLE_CAN_UPDATE_FEES: u8 = 1 << 1;
+pub const ROLE_CAN_SUSPEND_USERS: u8 = 1 << 2;
+pub const ROLE_CAN_SETTLE: u8 = 1 << 3;
+
+// How long a reserved tx_id stays exclusive to its owner
+pub const RESERVATION_TTL_SECONDS: i64 = 300;
+
+pub const BPS_DENOMINATOR: u64 = 10_000;
+
+// Weight of the running average; each new amount moves it by 1/N
+pub const VELOCITY_EWMA_WEIGHT: u64 = 4;
+
+// Longest parent chain returned by get_transaction_chain
+pub const MAX_CHAIN_DEPTH: usize = 16;
+
+// Most transactions close_transactions_batch closes in one instruction
+pub const MAX_CLOSE_BATCH: usize = 16;
+
+// Rough cost of one batch iteration, checked before starting it so a batch
+// that can't finish reverts with InsufficientCompute
+pub const CLOSE_BATCH_ITEM_COMPUTE_UNITS: u64 = 8_000;
+pub const BALANCE_BATCH_ITEM_COMPUTE_UNITS: u64 = 3_000;
+pub const RECONCILE_ITEM_COMPUTE_UNITS: u64 = 4_000;
+
+// Most summaries get_user_tx_page returns; keeps return data under 1KB
+pub const MAX_PAGE_SIZE: usize = 20;
+
+// Compute unit hints: a per-type base plus headroom for the extra checks
+// (approvals, guardians, budgets) larger amounts tend to hit
+pub const BASE_COMPUTE_UNITS_CRYPTO: u32 = 60_000;
+pub const BASE_COMPUTE_UNITS_AIRTIME: u32 = 40_000;
+pub const BASE_COMPUTE_UNITS_WITHDRAWAL: u32 = 40_000;
+pub const COMPUTE_TIERS: [(u64, u32); 2] = [(100_000, 10_000), (10_000_000, 25_000)];
+
+// Most users get_balances_batch reads in one call
+pub const MAX_BALANCE_BATCH: usize = 32;
+
+pub const SECONDS_PER_DAY: i64 = 86_400;
+pub const SECONDS_PER_WEEK: i64 = 7 * SECONDS_PER_DAY;
+pub const ALL_WEEKDAYS: u8 = 0b0111_1111;
+
+// Signature count, padding and one set of Ed25519Program offsets
+pub const ED25519_HEADER_LEN: usize = 16;
+
+// Where each Ed25519Program check sits, counted back from the instruction it
+// authorizes. The risk check is always last; a quote check precedes it.
+pub const RISK_SIGNATURE_SLOT: usize = 1;
+pub const QUOTE_SIGNATURE_SLOT: usize = 2;
+
+// SPL Token and Associated Token Account programs, for deriving user ATAs
+pub const TOKEN_PROGRAM_ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+pub const ASSOCIATED_TOKEN_PROGRAM_ID: Pubkey =
+    pubkey!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+// Default drift allowed between a client timestamp and the cluster clock
+pub const DEFAULT_MAX_CLIENT_SKEW: i64 = 300;
+
+// Highest priority hint accepted by create_transaction
+pub const MAX_PRIORITY: u8 = 3;
+
+// 1M USDC in base units (6 decimals)
+pub const MAX_AMOUNT_USDC: u64 = 1_000_000_000_000;
+
+// Fixed size of the opaque transaction note, so oversized blobs fail to deserialize
+pub const NOTE_CIPHERTEXT_LEN: usize = 64;
+pub const DISPLAY_NAME_LEN: usize = 32;
+pub const MAX_RECOVERY_GUARDIANS: usize = 5;
+pub const MAX_AIRTIME_DENOMINATIONS: usize = 8;
+
+// Config layout written by this build; bump whenever Config gains fields
+pub const CONFIG_VERSION: u8 = 20;
+
+// Spending categories a user can cap at once
+pub const MAX_CATEGORY_LIMITS: usize = 4;
+pub const LOYALTY_LEVELS: usize = 3;
+
+// Account Structures
+#[account]
+pub struct UserAccount {
+    pub phone_number: [u8; 14],
+    pub wallet: Pubkey,
+    pub pin_hash: [u8; 32],
+    pub total_volume: u64,
+    pub registered_at: i64,
+    pub status: UserStatus,
+    pub promo_enabled: bool,
+    pub promo_tx_count: u8,
+    pub available_balance: u64,
+    pub avg_amount: u64,
+    pub amount_samples: u8,
+    pub free_tx_remaining: u8,
+    pub tx_count: u64,
+    pub deposit_lamports: u64,
+    pub total_usdc_volume: u64,
+    pub notifications_enabled: bool,
+    pub bucketed: bool,
+    pub token_account: Pubkey,
+    pub micro_volume: u64,
+    pub rebate_owed: u64,
+    pub guardian: Option<Pubkey>,
+    pub pin_changed_at: i64,
+    pub display_name: [u8; DISPLAY_NAME_LEN],
+    pub recent_dispute_count: u8,
+    pub dispute_window_start: i64,
+    pub last_tx_timestamp: i64,
+    pub referrer_phone: Option<[u8; 14]>,
+    pub referral_rewards: u64,
+    pub loyalty_level: u8,
+    pub recovery_guardians: [Pubkey; MAX_RECOVERY_GUARDIANS],
+    pub recovery_threshold: u8,
+    pub tx_chain_hash: [u8; 32],
+    // USDC of crypto transactions opened but not yet settled
+    pub pending_usdc: u64,
+}
+
+#[account]
+pub struct TransactionAccount {
+    pub tx_id: [u8; 16],
+    pub user_phone: [u8; 14],
+    pub tx_type: TransactionType,
+    pub amount_ngn: u64,
+    pub amount_usdc: Option<u64>,
+    pub status: TransactionStatus,
+    pub timestamp: i64,
+    pub fee: u64,
+    pub archived: bool,
+    pub debited_amount: u64,
+    pub note_ciphertext: Option<[u8; NOTE_CIPHERTEXT_LEN]>,
+    pub applied_fee_bps: u16,
+    pub tip_ngn: u64,
+    pub parent_tx_id: Option<[u8; 16]>,
+    pub user_seq: u64,
+    pub retry_count: u8,
+    pub external_ref: Option<Pubkey>,
+    pub priority: u8,
+    // Portion of the fee held from the available balance
+    pub held_fee: u64,
+    pub fee_rebate: u64,
+    pub config_epoch: u64,
+    pub approval_nonce: [u8; 32],
+    pub approved: bool,
+    pub client_timestamp: Option<i64>,
+    pub risk_nonce: Option<[u8; 16]>,
+    pub quoted_rate: Option<u64>,
+    pub refunded_amount: u64,
+    // What settlement credited to the user's volume, less refunds since
+    pub accrued_volume: u64,
+    // Until then a failed transaction can still be completed
+    pub fail_grace_until: i64,
+    // Exact amount held from the balance at creation, bucketed or not
+    pub held_amount: u64,
+    // Whether settlement credited micro_volume rather than total_volume
+    pub accrued_to_micro: bool,
+}
+
+#[account]
+pub struct Config {
+    pub admin: Pubkey,
+    pub crypto_fee_bps: u16,
+    pub airtime_fee_bps: u16,
+    pub min_limit: u64,
+    pub max_limit: u64,
+    pub paused: bool,
+    pub promo_min_limit: u64,
+    pub max_promo_transactions: u8,
+    pub debug_logging: bool,
+    pub balance_enforced: bool,
+    pub crypto_settler: Pubkey,
+    pub airtime_settler: Pubkey,
+    pub allowed_airtime_denominations: [u64; MAX_AIRTIME_DENOMINATIONS],
+    pub new_tx_frozen: bool,
+    pub total_users: u64,
+    pub max_users: u64,
+    pub tip_recipient: Pubkey,
+    pub max_tip_ngn: u64,
+    pub total_tips: u64,
+    pub velocity_multiplier: u16,
+    pub velocity_min_history: u8,
+    pub free_tx_count: u8,
+    pub usdc_min_unit: u64,
+    pub restricted_tx_types: u8,
+    pub business_days: u8,
+    pub business_hours: Option<[u8; 2]>,
+    pub initialized_at: i64,
+    pub max_retries: u8,
+    pub registration_deposit: u64,
+    pub partner_program: Pubkey,
+    pub max_user_usdc_exposure: u64,
+    pub airtime_fee_free_below: u64,
+    pub limit_timelock: i64,
+    pub pending_max_limit: u64,
+    pub max_limit_unlock_at: i64,
+    pub ngn_symbol: [u8; 4],
+    pub ngn_decimals: u8,
+    pub usdc_decimals: u8,
+    pub backup_admin: Pubkey,
+    pub admin_last_seen: i64,
+    pub admin_timeout: i64,
+    pub bucket_size: u64,
+    pub usdc_mint: Pubkey,
+    pub min_volume_accrual: u64,
+    pub failure_rebate_bps: u16,
+    pub config_epoch: u64,
+    pub allow_suspended_withdrawal: bool,
+    pub dual_sign_threshold: u64,
+    pub env_id: u8,
+    pub risk_approver: Pubkey,
+    pub approval_threshold: u64,
+    pub config_version: u8,
+    pub emit_crypto_events: bool,
+    pub emit_airtime_events: bool,
+    pub min_fee_ngn: u64,
+    pub max_fee_ngn: u64,
+    pub min_pin_change_interval: i64,
+    pub max_client_skew: i64,
+    pub max_epoch_lag: u64,
+    pub max_disputes: u8,
+    pub dispute_window: i64,
+    pub limit_multiplier_bps: u16,
+    pub risk_checks_enabled: bool,
+    pub settled_count: u64,
+    pub total_settlement_seconds: u64,
+    pub preserve_on_close: bool,
+    pub referrer_fee_share_bps: u16,
+    pub unique_wallets: bool,
+    pub loyalty_thresholds: [u64; LOYALTY_LEVELS],
+    pub loyalty_limit_step_bps: u16,
+    pub pricing_key: Pubkey,
+    pub max_fee_ratio_bps: u16,
+    pub min_settlement_delay: i64,
+    pub accrue_net: bool,
+    pub max_amount_digits: u8,
+    pub fail_grace_period: i64,
+}
+
+#[account]
+pub struct TxReservation {
+    pub tx_id: [u8; 16],
+    pub owner: Pubkey,
+    pub expires_at: i64,
+}
+
+// Fee and limit values in force from `epoch` until the next update_config
+#[account]
+pub struct ConfigSnapshot {
+    pub epoch: u64,
+    pub crypto_fee_bps: u16,
+    pub airtime_fee_bps: u16,
+    pub min_limit: u64,
+    pub max_limit: u64,
+    pub timestamp: i64,
+}
+
+// Scoped permissions delegated by the super-admin to an operator key
+#[account]
+pub struct Role {
+    pub grantee: Pubkey,
+    pub permissions: u8,
+}
+
+// Guardian approvals gathered toward moving a user to new_wallet, one bit
+// per slot in recovery_guardians
+#[account]
+pub struct RecoveryRequest {
+    pub phone_number: [u8; 14],
+    pub new_wallet: Pubkey,
+    pub approvals: u8,
+    pub guardian_set: [u8; 32],
+    pub created_at: i64,
+}
+
+// Completions settled during one UTC day, for reporting. Created by the first
+// completion of the day, forced or not.
+#[account]
+pub struct DailyStats {
+    pub day: u32,
+    pub total_volume: u64,
+    pub tx_count: u64,
+}
+
+// Claims a wallet for one phone while unique_wallets is on. Closed along
+// with the user account to free the wallet again.
+#[account]
+pub struct WalletIndex {
+    pub wallet: Pubkey,
+    pub phone_number: [u8; 14],
+}
+
+// What survives close_user when preserve_on_close is on. Keyed by a hash of
+// the phone so a closed number isn't readable from the address.
+#[account]
+pub struct UserArchive {
+    pub phone_hash: [u8; 32],
+    pub total_volume: u64,
+    pub registered_at: i64,
+}
+
+// Self-imposed weekly spending caps, one slot per category
+#[account]
+pub struct UserBudget {
+    pub phone_number: [u8; 14],
+    pub limits: [CategoryLimit; MAX_CATEGORY_LIMITS],
+}
+
+// A zero weekly_limit marks a free slot
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct CategoryLimit {
+    pub category: u8,
+    pub weekly_limit: u64,
+    pub spent: u64,
+    pub week_start: i64,
+}
+
+// Enums
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum UserStatus {
+    Active,
+    Suspended,
+    Banned,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum TransactionType {
+    Crypto,
+    Airtime,
+    Withdrawal,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+pub enum TransactionStatus {
+    Pending,
+    Completed,
+    Failed,
+    Cancelled,
+    Disputed,
+    Held,
+    Refunded,
+}
+
+// View Types
+// Everything update_config_atomic sets
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ConfigParams {
+    pub crypto_fee_bps: u16,
+    pub airtime_fee_bps: u16,
+    pub min_limit: u64,
+    pub max_limit: u64,
+    pub min_fee_ngn: u64,
+    pub max_fee_ngn: u64,
+    pub failure_rebate_bps: u16,
+    pub referrer_fee_share_bps: u16,
+    pub limit_multiplier_bps: u16,
+}
+
+// NGN per whole USDC, good until expires_at
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PriceQuote {
+    pub rate: u64,
+    pub expires_at: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SimulationResult {
+    pub would_succeed: bool,
+    // Anchor error code of the first failing check, zero on success
+    pub reason_code: u32,
+    pub fee: u64,
+    // Available balance after this transaction's debit
+    pub remaining_balance: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct TransactionSummary {
+    pub tx_id: [u8; 16],
+    pub user_seq: u64,
+    pub tx_type: TransactionType,
+    pub status: TransactionStatus,
+    pub amount_ngn: u64,
+    pub timestamp: i64,
+    pub archived: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct VolumeAttestation {
+    pub phone: [u8; 14],
+    pub total_volume: u64,
+    pub timestamp: i64,
+    // sha256(program id || config || phone || total_volume || timestamp),
+    // integers little-endian
+    pub digest: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct UserStats {
+    pub phone: [u8; 14],
+    pub total_volume: u64,
+    pub tx_count: u64,
+    pub loyalty_level: u8,
+    pub tx_chain_hash: [u8; 32],
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct UserBalanceEntry {
+    pub phone: [u8; 14],
+    pub total_volume: u64,
+    pub status: UserStatus,
+}
+
+// Account Size Implementations
+impl UserAccount {
+    pub const LEN: usize = 8 + 14 + 32 + 32 + 8 + 8 + 1 + 1 + 1 + 8 + 8 + 1 + 1 + 8 + 8 + 8 + 1 + 1
+        + 32 + 8 + 8 + 33 + 8 + DISPLAY_NAME_LEN + 1 + 8 + 8
+        + 15 + 8 + 1 + 32 * MAX_RECOVERY_GUARDIANS + 1
+        + 32 + 8; //512 bytes
+    
+    // Ties recovery approvals to the guardian set they were given under
+    pub fn guardian_set_hash(&self) -> [u8; 32] {
+        let keys: Vec<&[u8]> = self.recovery_guardians.iter().map(|key| key.as_ref()).collect();
+        hashv(&[&keys.concat(), &[self.recovery_threshold]]).to_bytes()
+    }
+    
+    pub fn record_amount(&mut self, amount_ngn: u64) {
+        self.avg_amount = if self.amount_samples == 0 {
+            amount_ngn
+        } else {
+            let weight = VELOCITY_EWMA_WEIGHT as u128;
+            let weighted = (self.avg_amount as u128) * (weight - 1) + amount_ngn as u128;
+            (weighted / weight) as u64
+        };
+        self.amount_samples = self.amount_samples.saturating_add(1);
+    }
+    
+    // A pending transaction's USDC counts towards the exposure cap until it
+    // settles; see validate_transaction
+    pub fn hold_pending_usdc(&mut self, transaction: &TransactionAccount) -> Result<()> {
+        self.pending_usdc = self.pending_usdc
+            .checked_add(transaction.amount_usdc.unwrap_or(0))
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        Ok(())
+    }
+    
+    // Saturating, since a transaction opened before pending_usdc existed was
+    // never added to it
+    pub fn release_pending_usdc(&mut self, transaction: &TransactionAccount) {
+        self.pending_usdc = self.pending_usdc
+            .saturating_sub(transaction.amount_usdc.unwrap_or(0));
+    }
+}
+
+impl TransactionAccount {
+    pub const LEN: usize = 8 + 16 + 14 + 1 + 8 + 9 + 1 + 8 + 8 + 1 + 8
+        + 1 + NOTE_CIPHERTEXT_LEN + 2 + 8 + 17 + 8 + 1 + 33 + 1 + 8 + 8 + 8
+        + 32 + 1 + 9 + 17 + 9 + 8 + 8 + 8 + 8 + 1;
+    
+    pub fn awaits_approval(&self) -> bool {
+        self.approval_nonce != [0; 32] && !self.approved
+    }
+    
+    // USDC share of refunded_amount, rounded down. Successive partial refunds
+    // add up to exactly amount_usdc once the whole amount is refunded.
+    pub fn refunded_usdc(&self) -> u64 {
+        let Some(amount_usdc) = self.amount_usdc else {
+            return 0;
+        };
+        let share = (amount_usdc as u128) * (self.refunded_amount as u128)
+            / (self.amount_ngn as u128);
+        share as u64
+    }
+    
+    pub fn is_settled(&self) -> bool {
+        matches!(
+            self.status,
+            TransactionStatus::Completed
+                | TransactionStatus::Failed
+                | TransactionStatus::Cancelled
+                | TransactionStatus::Refunded
+        )
+    }
+}
+
+impl Config {
+    pub const LEN: usize = 8 + 32 + 2 + 2 + 8 + 8 + 1 + 8 + 1 + 1 + 1 + 32 + 32
+        + 8 * MAX_AIRTIME_DENOMINATIONS + 1 + 8 + 8 + 32 + 8 + 8
+        + 2 + 1 + 1 + 8 + 1 + 1 + 3 + 8 + 1 + 8 + 32 + 8 + 8
+        + 8 + 8 + 8 + 4 + 1 + 1 + 32 + 8 + 8 + 8 + 32 + 8 + 2 + 8
+        + 1 + 8 + 1 + 32 + 8 + 1 + 1 + 1 + 8 + 8 + 8 + 8 + 8
+        + 1 + 8 + 2 + 1 + 8 + 8 + 1 + 2 + 1 + 8 * LOYALTY_LEVELS + 2
+        + 32 + 2 + 8 + 1 + 1 + 8;
+    
+    pub fn env_seed(&self) -> &[u8] {
+        config_env_seed(&self.env_id)
+    }
+    
+    // For migrate_config: fields added after `from_version` get the values
+    // initialize_config gives them, keyed by the version that added each
+    pub fn apply_defaults_since(&mut self, from_version: u8, now: i64) {
+        // An unversioned config can come from any layout before versioning,
+        // so a field from then is only defaulted while it still reads zero
+        if from_version < 1 {
+            self.apply_unversioned_defaults(now);
+        }
+        if from_version < 2 {
+            self.emit_crypto_events = true;
+            self.emit_airtime_events = true;
+        }
+        if from_version < 5 {
+            self.max_client_skew = DEFAULT_MAX_CLIENT_SKEW;
+        }
+        if from_version < 8 {
+            self.limit_multiplier_bps = BPS_DENOMINATOR as u16;
+        }
+    }
+    
+    fn apply_unversioned_defaults(&mut self, now: i64) {
+        if self.promo_min_limit == 0 {
+            self.promo_min_limit = self.min_limit;
+        }
+        let admin = self.admin;
+        for key in [&mut self.crypto_settler, &mut self.airtime_settler, &mut self.tip_recipient] {
+            if *key == Pubkey::default() {
+                *key = admin;
+            }
+        }
+        // The weekday mask only matters once a type is restricted, so an
+        // unrestricted config can safely take the default
+        if self.restricted_tx_types == 0 && self.business_days == 0 {
+            self.business_days = ALL_WEEKDAYS;
+        }
+        if self.initialized_at == 0 {
+            self.initialized_at = now;
+        }
+        // The display metadata is always set together
+        if self.ngn_symbol == [0; 4] && self.ngn_decimals == 0 && self.usdc_decimals == 0 {
+            self.ngn_symbol = *b"NGN\0";
+            self.ngn_decimals = 2;
+            self.usdc_decimals = 6;
+        }
+        if self.admin_last_seen == 0 {
+            self.admin_last_seen = now;
+        }
+    }
+    
+    // Each transaction type is settled by its own operational key. Cash-outs
+    // are paid by the admin's treasury operations.
+    pub fn settler_for(&self, tx_type: &TransactionType) -> Pubkey {
+        match tx_type {
+            TransactionType::Crypto => self.crypto_settler,
+            TransactionType::Airtime => self.airtime_settler,
+            TransactionType::Withdrawal => self.admin,
+        }
+    }
+    
+    // Withdrawing one's own balance is free
+    pub fn fee_bps_for(&self, tx_type: &TransactionType) -> u16 {
+        match tx_type {
+            TransactionType::Crypto => self.crypto_fee_bps,
+            TransactionType::Airtime => self.airtime_fee_bps,
+            TransactionType::Withdrawal => 0,
+        }
+    }
+    
+    // Stored limits scaled by the global multiplier, saturating at u64::MAX
+    fn scale_limit(&self, limit: u64) -> u64 {
+        let scaled = (limit as u128) * (self.limit_multiplier_bps as u128)
+            / (BPS_DENOMINATOR as u128);
+        u64::try_from(scaled).unwrap_or(u64::MAX)
+    }
+    
+    pub fn effective_min_limit(&self) -> u64 {
+        self.scale_limit(self.min_limit)
+    }
+    
+    pub fn effective_max_limit(&self) -> u64 {
+        self.scale_limit(self.max_limit)
+    }
+    
+    // effective_max_limit raised by loyalty_limit_step_bps per level
+    pub fn max_limit_for(&self, loyalty_level: u8) -> u64 {
+        let bps = BPS_DENOMINATOR as u128
+            + (self.loyalty_limit_step_bps as u128) * (loyalty_level as u128);
+        let raised = (self.effective_max_limit() as u128) * bps / (BPS_DENOMINATOR as u128);
+        u64::try_from(raised).unwrap_or(u64::MAX)
+    }
+    
+    // Highest level whose threshold the volume has reached
+    pub fn loyalty_level_for(&self, total_volume: u64) -> u8 {
+        self.loyalty_thresholds
+            .iter()
+            .take_while(|&&threshold| threshold > 0 && total_volume >= threshold)
+            .count() as u8
+    }
+    
+    // Volume a completion credits. Net is an exact subtraction, so net plus
+    // the fee is always the gross amount and no rounding dust goes missing;
+    // validate_transaction already keeps the fee within the amount.
+    pub fn accrued_volume(&self, amount_ngn: u64, fee: u64) -> u64 {
+        if self.accrue_net {
+            amount_ngn.saturating_sub(fee)
+        } else {
+            amount_ngn
+        }
+    }
+    
+    // Raises a tiny percentage fee to the floor, then applies the cap
+    pub fn bound_fee(&self, fee: u64) -> u64 {
+        let fee = fee.max(self.min_fee_ngn);
+        if self.max_fee_ngn > 0 {
+            fee.min(self.max_fee_ngn)
+        } else {
+            fee
+        }
+    }
+    
+    pub fn emits_events_for(&self, tx_type: &TransactionType) -> bool {
+        match tx_type {
+            TransactionType::Crypto => self.emit_crypto_events,
+            TransactionType::Airtime => self.emit_airtime_events,
+            TransactionType::Withdrawal => true,
+        }
+    }
+    
+    // A withdrawal pays out of the available balance even when enforcement
+    // is off
+    pub fn holds_balance(&self, tx_type: &TransactionType) -> bool {
+        self.balance_enforced || *tx_type == TransactionType::Withdrawal
+    }
+    
+    pub fn is_allowed_airtime_denomination(&self, amount_ngn: u64) -> bool {
+        let denominations = &self.allowed_airtime_denominations;
+        if denominations.iter().all(|&denomination| denomination == 0) {
+            return true;
+        }
+        amount_ngn > 0 && denominations.contains(&amount_ngn)
+    }
+    
+    // Only types flagged in restricted_tx_types are held to the window
+    pub fn is_within_business_hours(&self, tx_type: &TransactionType, now: i64) -> bool {
+        if self.restricted_tx_types & tx_type.flag() == 0 {
+            return true;
+        }
+        
+        // 1970-01-01 was a Thursday
+        let days = now.div_euclid(SECONDS_PER_DAY);
+        let weekday = (days + 4).rem_euclid(7) as u8;
+        if self.business_days & (1 << weekday) == 0 {
+            return false;
+        }
+        
+        // [start, end) in UTC hours; a start after the end wraps past midnight
+        let Some([start, end]) = self.business_hours else {
+            return true;
+        };
+        let hour = (now.rem_euclid(SECONDS_PER_DAY) / 3600) as u8;
+        if start <= end {
+            hour >= start && hour < end
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+impl ConfigParams {
+    // The same rules the individual setters enforce
+    pub fn validate(&self) -> Result<()> {
+        let bps_in_range = [
+            self.crypto_fee_bps,
+            self.airtime_fee_bps,
+            self.failure_rebate_bps,
+            self.referrer_fee_share_bps,
+        ]
+        .iter()
+        .all(|&bps| bps as u64 <= BPS_DENOMINATOR);
+        let fee_bounds_ok = self.max_fee_ngn == 0 || self.min_fee_ngn <= self.max_fee_ngn;
+        if !bps_in_range
+            || !fee_bounds_ok
+            || self.min_limit > self.max_limit
+            || self.limit_multiplier_bps == 0
+        {
+            return Err(OuhError::InvalidConfig.into());
+        }
+        Ok(())
+    }
+}
+
+impl PriceQuote {
+    // USDC base units bought by amount_ngn at the quoted rate, rounded down
+    pub fn usdc_for(&self, amount_ngn: u64, usdc_decimals: u8) -> Result<u64> {
+        if self.rate == 0 {
+            return Err(OuhError::InvalidQuote.into());
+        }
+        let scale = 10u128.pow(usdc_decimals as u32);
+        let usdc = (amount_ngn as u128) * scale / (self.rate as u128);
+        u64::try_from(usdc).map_err(|_| error!(OuhError::InvalidQuote))
+    }
+}
+
+impl TransactionType {
+    pub fn flag(&self) -> u8 {
+        match self {
+            TransactionType::Crypto => 1 << 0,
+            TransactionType::Airtime => 1 << 1,
+            TransactionType::Withdrawal => 1 << 2,
+        }
+    }
+}
+
+impl TxReservation {
+    pub const LEN: usize = 8 + 16 + 32 + 8;
+}
+
+impl ConfigSnapshot {
+    pub const LEN: usize = 8 + 8 + 2 + 2 + 8 + 8 + 8;
+    
+    pub fn record(&mut self, config: &Config, timestamp: i64) {
+        self.epoch = config.config_epoch;
+        self.crypto_fee_bps = config.crypto_fee_bps;
+        self.airtime_fee_bps = config.airtime_fee_bps;
+        self.min_limit = config.min_limit;
+        self.max_limit = config.max_limit;
+        self.timestamp = timestamp;
+    }
+}
+
+impl Role {
+    pub const LEN: usize = 8 + 32 + 1;
+    
+    pub fn has(&self, permission: u8) -> bool {
+        self.permissions & permission == permission
+    }
+}
+
+impl RecoveryRequest {
+    pub const LEN: usize = 8 + 14 + 32 + 1 + 32 + 8;
+}
+
+impl DailyStats {
+    pub const LEN: usize = 8 + 4 + 8 + 8;
+}
+
+impl WalletIndex {
+    pub const LEN: usize = 8 + 32 + 14;
+}
+
+impl UserArchive {
+    pub const LEN: usize = 8 + 32 + 8 + 8;
+}
+
+impl UserBudget {
+    pub const LEN: usize = 8 + 14 + (1 + 8 + 8 + 8) * MAX_CATEGORY_LIMITS;
+    
+    fn find(&self, category: u8) -> Option<usize> {
+        self.limits
+            .iter()
+            .position(|limit| limit.weekly_limit > 0 && limit.category == category)
+    }
+    
+    // Counts `amount` against the category's cap, starting a fresh week once
+    // the current one has run out. Uncapped categories always pass.
+    pub fn charge(&mut self, category: u8, amount: u64, now: i64) -> Result<()> {
+        let Some(index) = self.find(category) else {
+            return Ok(());
+        };
+        let limit = &mut self.limits[index];
+        if safe_elapsed(now, limit.week_start) >= SECONDS_PER_WEEK {
+            limit.spent = 0;
+            limit.week_start = now;
+        }
+        
+        let spent = limit.spent.checked_add(amount).ok_or(OuhError::ArithmeticOverflow)?;
+        if spent > limit.weekly_limit {
+            return Err(OuhError::CategoryLimitExceeded.into());
+        }
+        limit.spent = spent;
+        Ok(())
+    }
+}
+
+// Helpers
+
+// The default environment keeps the original seedless config address; other
+// environments append their id so each gets its own Config PDA.
+pub fn config_env_seed(env_id: &u8) -> &[u8] {
+    if *env_id == 0 {
+        &[]
+    } else {
+        std::slice::from_ref(env_id)
+    }
+}
+
+// Cluster time, rejecting the zero or negative timestamps a misconfigured
+// local validator can report; they would corrupt stored dates and windows.
+pub fn current_timestamp() -> Result<i64> {
+    let now = Clock::get()?.unix_timestamp;
+    if now <= 0 {
+        return Err(OuhError::InvalidClock.into());
+    }
+    Ok(now)
+}
+
+// The instruction `slot` places before this one must be an Ed25519Program
+// check of `signature` by `signer` over exactly `message`. The runtime rejects
+// the transaction if that check fails, so only its contents need matching here.
+fn require_ed25519_signature(
+    instructions: &AccountInfo,
+    slot: usize,
+    signer: &Pubkey,
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<()> {
+    let current = load_current_index_checked(instructions)? as usize;
+    let Some(index) = current.checked_sub(slot) else {
+        return Err(OuhError::InvalidSignature.into());
+    };
+    let ix = load_instruction_at_checked(index, instructions)?;
+    if ix.program_id != ed25519_program::ID {
+        return Err(OuhError::InvalidSignature.into());
+    }
+    
+    // One signature whose offsets all point into the instruction's own data
+    let data = &ix.data;
+    if data.len() < ED25519_HEADER_LEN || data[0] != 1 {
+        return Err(OuhError::InvalidSignature.into());
+    }
+    let read_u16 = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]);
+    if [4, 8, 14].iter().any(|&at| read_u16(at) != u16::MAX) {
+        return Err(OuhError::InvalidSignature.into());
+    }
+    let signature_offset = read_u16(2) as usize;
+    let public_key_offset = read_u16(6) as usize;
+    let message_offset = read_u16(10) as usize;
+    let message_size = read_u16(12) as usize;
+    
+    let matches = data.get(signature_offset..signature_offset + 64) == Some(&signature[..])
+        && data.get(public_key_offset..public_key_offset + 32) == Some(signer.as_ref())
+        && message_size == message.len()
+        && data.get(message_offset..message_offset + message_size) == Some(message);
+    if !matches {
+        return Err(OuhError::InvalidSignature.into());
+    }
+    Ok(())
+}
+
+// Client clocks can't be trusted; anything further than max_client_skew from
+// the cluster clock in either direction is rejected
+pub fn require_within_skew(
+    config: &Config,
+    client_timestamp: i64,
+    now: i64,
+) -> std::result::Result<(), OuhError> {
+    if client_timestamp.abs_diff(now) > config.max_client_skew.unsigned_abs() {
+        return Err(OuhError::TimestampSkew);
+    }
+    Ok(())
+}
+
+// Trims surrounding whitespace and re-pads with zeros. Zero bytes may only
+// pad the end, and the text must be UTF-8 without control characters.
+pub fn normalize_display_name(name: &[u8; DISPLAY_NAME_LEN]) -> Result<[u8; DISPLAY_NAME_LEN]> {
+    let len = name.iter().rposition(|&byte| byte != 0).map_or(0, |last| last + 1);
+    let text = std::str::from_utf8(&name[..len])
+        .map_err(|_| OuhError::InvalidDisplayName)?;
+    if text.chars().any(char::is_control) {
+        return Err(OuhError::InvalidDisplayName.into());
+    }
+    
+    let trimmed = text.trim().as_bytes();
+    let mut normalized = [0; DISPLAY_NAME_LEN];
+    normalized[..trimmed.len()].copy_from_slice(trimmed);
+    Ok(normalized)
+}
+
+// Headroom comes from the highest tier the amount reaches
+pub fn recommended_compute_units(tx_type: TransactionType, amount_ngn: u64) -> u32 {
+    let base = match tx_type {
+        TransactionType::Crypto => BASE_COMPUTE_UNITS_CRYPTO,
+        TransactionType::Airtime => BASE_COMPUTE_UNITS_AIRTIME,
+        TransactionType::Withdrawal => BASE_COMPUTE_UNITS_WITHDRAWAL,
+    };
+    let headroom = COMPUTE_TIERS
+        .iter()
+        .rev()
+        .find(|&&(threshold, _)| amount_ngn >= threshold)
+        .map_or(0, |&(_, units)| units);
+    base + headroom
+}
+
+// Fills a freshly created wallet index. Its init is what rejects a wallet
+// that already holds a phone.
+fn index_wallet(
+    config: &Config,
+    wallet_index: Option<&mut WalletIndex>,
+    wallet: Pubkey,
+    phone_number: [u8; 14],
+) -> Result<()> {
+    match wallet_index {
+        Some(index) => {
+            index.wallet = wallet;
+            index.phone_number = phone_number;
+            Ok(())
+        }
+        None if config.unique_wallets => Err(OuhError::WalletIndexRequired.into()),
+        None => Ok(()),
+    }
+}
+
+// Days since the unix epoch; the DailyStats seed
+pub fn utc_day(now: i64) -> u32 {
+    now.div_euclid(SECONDS_PER_DAY) as u32
+}
+
+pub fn phone_hash(phone_number: &[u8; 14]) -> [u8; 32] {
+    hashv(&[phone_number]).to_bytes()
+}
+
+// Decimal digits once trailing zeros are dropped: 1_500 has two
+pub fn significant_digits(amount: u64) -> u32 {
+    amount.to_string().trim_end_matches('0').len() as u32
+}
+
+// new - old, saturating at the i64 range
+fn signed_delta(old: u64, new: u64) -> i64 {
+    let delta = new as i128 - old as i128;
+    i64::try_from(delta).unwrap_or(if delta < 0 { i64::MIN } else { i64::MAX })
+}
+
+fn require_compute(needed: u64) -> Result<()> {
+    if sol_remaining_compute_units() < needed {
+        return Err(OuhError::InsufficientCompute.into());
+    }
+    Ok(())
+}
+
+// Seconds elapsed since `then`. The cluster clock can step backwards across
+// forks; a backward jump counts as no time elapsed rather than a negative span.
+pub fn safe_elapsed(now: i64, then: i64) -> i64 {
+    now.saturating_sub(then).max(0)
+}
+
+// The super-admin holds every permission; anyone else needs a role with the bit set
+fn require_permission(
+    config: &Config,
+    authority: &Pubkey,
+    role: &Option<Account<Role>>,
+    permission: u8,
+) -> Result<()> {
+    if *authority == config.admin {
+        return Ok(());
+    }
+    match role {
+        Some(role) if role.has(permission) => Ok(()),
+        _ => Err(OuhError::Unauthorized.into()),
+    }
+}
+
+// Transactions are settled by the key for their type, or by a delegated
+// settle role
+fn require_settler(
+    config: &Config,
+    authority: &Pubkey,
+    role: &Option<Account<Role>>,
+    tx_type: &TransactionType,
+) -> Result<()> {
+    let is_settler = *authority == config.settler_for(tx_type);
+    let has_role = role.as_ref().is_some_and(|role| role.has(ROLE_CAN_SETTLE));
+    if !is_settler && !has_role {
+        return Err(OuhError::Unauthorized.into());
+    }
+    Ok(())
+}
+
+impl CreateTransaction<'_> {
+    // Context checks shared by create_transaction and
+    // create_transaction_with_quote, then the write itself
+    fn open(
+        &mut self,
+        mut new: NewTransaction,
+        category: Option<u8>,
+        risk_signature: Option<[u8; 64]>,
+        now: i64,
+    ) -> Result<()> {
+        let config = &mut self.config;
+        
+        // The reservation is closed by the account constraints once this succeeds
+        if now >= self.reservation.expires_at {
+            return Err(OuhError::ReservationExpired.into());
+        }
+        
+        // A derived transaction (refund, retry, ...) must point at a settled
+        // transaction of the same user
+        let parent = self.parent_transaction.as_ref();
+        match (new.parent_tx_id, parent) {
+            (None, None) => {}
+            (Some(parent_tx_id), Some(parent))
+                if parent.tx_id == parent_tx_id
+                    && parent.user_phone == new.user_phone
+                    && parent.is_settled() => {}
+            _ => return Err(OuhError::InvalidParentTransaction.into()),
+        }
+        
+        // Users with a guardian need its co-signature above the threshold
+        if let Some(guardian) = self.user_account.guardian {
+            let large = config.dual_sign_threshold > 0 && new.amount_ngn > config.dual_sign_threshold;
+            let cosigned = self.guardian
+                .as_ref()
+                .is_some_and(|signer| signer.key() == guardian);
+            if large && !cosigned {
+                return Err(OuhError::GuardianSignatureRequired.into());
+            }
+        }
+        
+        // The risk engine scores the request off-chain and signs
+        // tx_id || nonce; the nonce is kept on the transaction
+        if config.risk_checks_enabled {
+            let (Some(nonce), Some(signature), Some(instructions)) =
+                (new.risk_nonce, risk_signature, self.instructions.as_ref())
+            else {
+                return Err(OuhError::RiskCheckFailed.into());
+            };
+            let message = [new.tx_id.as_ref(), nonce.as_ref()].concat();
+            require_ed25519_signature(
+                instructions,
+                RISK_SIGNATURE_SLOT,
+                &config.risk_approver,
+                &message,
+                &signature,
+            )
+            .map_err(|_| error!(OuhError::RiskCheckFailed))?;
+        }
+        
+        // Only caps the user set on their own budget apply
+        if let (Some(category), Some(budget)) = (category, self.user_budget.as_mut()) {
+            budget.charge(category, new.amount_ngn, now)?;
+        }
+        
+        // Merchant orders live in the partner program; only the owner is checked
+        new.external_ref = match self.external_ref.as_ref() {
+            Some(order) => {
+                if config.partner_program == Pubkey::default()
+                    || *order.owner != config.partner_program
+                {
+                    return Err(OuhError::InvalidExternalRef.into());
+                }
+                Some(order.key())
+            }
+            None => None,
+        };
+        
+        open_transaction(
+            &mut self.transaction_account,
+            &mut self.user_account,
+            config,
+            new,
+            now,
+        )
+    }
+}
+
+impl CompleteTransaction<'_> {
+    pub fn require_settler(&self) -> Result<()> {
+        let tx_type = &self.transaction_account.tx_type;
+        require_settler(&self.config, &self.authority.key(), &self.role, tx_type)
+    }
+}
+
+impl UpdateTransactionStatus<'_> {
+    pub fn require_settler(&self) -> Result<()> {
+        let tx_type = &self.transaction_account.tx_type;
+        require_settler(&self.config, &self.authority.key(), &self.role, tx_type)
+    }
+}
+
+impl UpdateConfig<'_> {
+    // Feeds the backup-admin inactivity timeout
+    pub fn touch_admin(&mut self) -> Result<()> {
+        self.config.admin_last_seen = current_timestamp()?;
+        Ok(())
+    }
+}
+
+impl DelegatedConfigUpdate<'_> {
+    pub fn require(&mut self, permission: u8) -> Result<()> {
+        require_permission(&self.config, &self.authority.key(), &self.role, permission)?;
+        if self.authority.key() == self.config.admin {
+            self.config.admin_last_seen = current_timestamp()?;
+        }
+        Ok(())
+    }
+}
+
+impl UpdateFeeConfig<'_> {
+    pub fn require(&mut self, permission: u8) -> Result<()> {
+        require_permission(&self.config, &self.authority.key(), &self.role, permission)?;
+        if self.authority.key() == self.config.admin {
+            self.config.admin_last_seen = current_timestamp()?;
+        }
+        Ok(())
+    }
+    
+    // For instructions no role can be delegated; any role account passed is
+    // ignored
+    pub fn require_admin(&mut self) -> Result<()> {
+        if self.authority.key() != self.config.admin {
+            return Err(OuhError::Unauthorized.into());
+        }
+        self.config.admin_last_seen = current_timestamp()?;
+        Ok(())
+    }
+}
+
+impl DelegatedUserUpdate<'_> {
+    pub fn require(&self, permission: u8) -> Result<()> {
+        require_permission(&self.config, &self.authority.key(), &self.role, permission)
+    }
+}
+
+struct FeeQuote {
+    is_promo: bool,
+    fee_bps: u16,
+    fee: u64,
+}
+
+// The caller-chosen parts of a transaction that validate_transaction judges
+struct TransactionTerms {
+    tx_type: TransactionType,
+    amount_ngn: u64,
+    amount_usdc: Option<u64>,
+    tip_ngn: u64,
+    priority: u8,
+    client_timestamp: Option<i64>,
+}
+
+struct NewTransaction {
+    tx_id: [u8; 16],
+    user_phone: [u8; 14],
+    tx_type: TransactionType,
+    amount_ngn: u64,
+    amount_usdc: Option<u64>,
+    note_ciphertext: Option<[u8; NOTE_CIPHERTEXT_LEN]>,
+    tip_ngn: Option<u64>,
+    parent_tx_id: Option<[u8; 16]>,
+    priority: u8,
+    client_timestamp: Option<i64>,
+    external_ref: Option<Pubkey>,
+    risk_nonce: Option<[u8; 16]>,
+    quoted_rate: Option<u64>,
+}
+
+// Raising max_limit waits out the timelock; lowering it is safe to apply at
+// once and drops any pending increase
+fn set_fee_terms(
+    config: &mut Config,
+    crypto_fee_bps: u16,
+    airtime_fee_bps: u16,
+    min_limit: u64,
+    max_limit: u64,
+) -> Result<()> {
+    let timelocked = max_limit > config.max_limit && config.limit_timelock > 0;
+    if timelocked && min_limit > config.max_limit {
+        return Err(OuhError::InvalidConfig.into());
+    }
+    
+    config.crypto_fee_bps = crypto_fee_bps;
+    config.airtime_fee_bps = airtime_fee_bps;
+    config.min_limit = min_limit;
+    if timelocked {
+        let now = current_timestamp()?;
+        config.pending_max_limit = max_limit;
+        config.max_limit_unlock_at = now
+            .checked_add(config.limit_timelock)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+    } else {
+        config.max_limit = max_limit;
+        config.pending_max_limit = 0;
+        config.max_limit_unlock_at = 0;
+    }
+    Ok(())
+}
+
+// Shared by register_user and register_and_transact
+fn open_user<'info>(
+    user_account: &mut Account<'info, UserAccount>,
+    config: &mut Config,
+    user: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    phone_number: [u8; 14],
+    pin_hash: [u8; 32],
+) -> Result<()> {
+    if config.max_users > 0 && config.total_users >= config.max_users {
+        return Err(OuhError::RegistrationCapReached.into());
+    }
+    config.total_users = config.total_users.checked_add(1).ok_or(OuhError::ArithmeticOverflow)?;
+    
+    // The deposit sits in the user PDA on top of rent until close_user
+    let deposit = config.registration_deposit;
+    if deposit > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: user.to_account_info(),
+                    to: user_account.to_account_info(),
+                },
+            ),
+            deposit,
+        )?;
+    }
+    
+    user_account.phone_number = phone_number;
+    user_account.wallet = user.key();
+    user_account.pin_hash = pin_hash;
+    user_account.total_volume = 0;
+    user_account.registered_at = current_timestamp()?;
+    user_account.status = UserStatus::Active;
+    user_account.promo_enabled = false;
+    user_account.promo_tx_count = 0;
+    user_account.available_balance = 0;
+    user_account.avg_amount = 0;
+    user_account.amount_samples = 0;
+    user_account.free_tx_remaining = config.free_tx_count;
+    user_account.tx_count = 0;
+    user_account.deposit_lamports = deposit;
+    user_account.total_usdc_volume = 0;
+    user_account.notifications_enabled = false;
+    user_account.bucketed = false;
+    user_account.token_account = Pubkey::default();
+    user_account.micro_volume = 0;
+    user_account.rebate_owed = 0;
+    user_account.guardian = None;
+    // Never changed; the first change isn't held to the cooldown
+    user_account.pin_changed_at = 0;
+    user_account.display_name = [0; DISPLAY_NAME_LEN];
+    user_account.recent_dispute_count = 0;
+    user_account.dispute_window_start = 0;
+    user_account.last_tx_timestamp = 0;
+    user_account.referrer_phone = None;
+    user_account.referral_rewards = 0;
+    user_account.loyalty_level = 0;
+    user_account.recovery_guardians = [Pubkey::default(); MAX_RECOVERY_GUARDIANS];
+    user_account.recovery_threshold = 0;
+    user_account.tx_chain_hash = [0; 32];
+    user_account.pending_usdc = 0;
+    Ok(())
+}
+
+// Writes a new transaction after the context-bound checks (reservation,
+// parent, guardian, budget, partner order) have passed. Shared by
+// create_transaction and register_and_transact.
+fn open_transaction(
+    transaction: &mut TransactionAccount,
+    user_account: &mut UserAccount,
+    config: &mut Config,
+    new: NewTransaction,
+    now: i64,
+) -> Result<()> {
+    let NewTransaction {
+        tx_id,
+        user_phone,
+        tx_type,
+        amount_ngn,
+        amount_usdc,
+        note_ciphertext,
+        tip_ngn,
+        parent_tx_id,
+        priority,
+        client_timestamp,
+        external_ref,
+        risk_nonce,
+        quoted_rate,
+    } = new;
+    // The caller checks the nonce's signature against its instructions
+    // sysvar. A path with no way to do that passes no nonce and stops here.
+    if config.risk_checks_enabled && risk_nonce.is_none() {
+        return Err(OuhError::RiskCheckFailed.into());
+    }
+    
+    let terms = TransactionTerms {
+        tx_type,
+        amount_ngn,
+        amount_usdc,
+        tip_ngn: tip_ngn.unwrap_or(0),
+        priority,
+        client_timestamp,
+    };
+    let quote = validate_transaction(config, user_account, &terms, now)?;
+    let TransactionTerms { tx_type, tip_ngn, .. } = terms;
+    let fee = quote.fee;
+    
+    user_account.last_tx_timestamp = now;
+    config.total_tips = config.total_tips.checked_add(tip_ngn).ok_or(OuhError::ArithmeticOverflow)?;
+    
+    // With balance enforcement on, the amount plus fee and tip is taken
+    // from the user's available balance up front and recorded on the
+    // transaction.
+    let holds_balance = config.holds_balance(&tx_type);
+    let debited_amount = if holds_balance {
+        let total = amount_ngn
+            .checked_add(fee)
+            .and_then(|total| total.checked_add(tip_ngn))
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        user_account.available_balance = user_account.available_balance
+            .checked_sub(total)
+            .ok_or(OuhError::InsufficientBalance)?;
+        total
+    } else {
+        0
+    };
+    
+    user_account.record_amount(amount_ngn);
+    
+    // Volume and the stored amount use the bucketed value
+    let stored_amount = if user_account.bucketed {
+        bucket_amount(amount_ngn, config.bucket_size)
+    } else {
+        amount_ngn
+    };
+    
+    // Per-user sequence number, so clients can page through history
+    let user_seq = user_account.tx_count;
+    user_account.tx_count = user_account.tx_count
+        .checked_add(1)
+        .ok_or(OuhError::ArithmeticOverflow)?;
+    
+    // Promo transactions count towards the user's promo allowance
+    if quote.is_promo {
+        user_account.promo_tx_count = user_account.promo_tx_count
+            .checked_add(1)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        if user_account.promo_tx_count >= config.max_promo_transactions {
+            user_account.promo_enabled = false;
+            user_account.promo_tx_count = 0;
+        }
+    }
+    
+    transaction.tx_id = tx_id;
+    transaction.user_phone = user_phone;
+    transaction.tx_type = tx_type;
+    transaction.amount_ngn = stored_amount;
+    transaction.amount_usdc = amount_usdc;
+    transaction.status = TransactionStatus::Pending;
+    transaction.timestamp = now;
+    transaction.fee = fee;
+    transaction.applied_fee_bps = quote.fee_bps;
+    transaction.tip_ngn = tip_ngn;
+    transaction.archived = false;
+    transaction.debited_amount = debited_amount;
+    transaction.held_fee = if holds_balance { fee } else { 0 };
+    transaction.held_amount = if holds_balance { amount_ngn } else { 0 };
+    transaction.config_epoch = config.config_epoch;
+    transaction.fee_rebate = 0;
+    // Encrypted client-side; stored as-is and never rewritten
+    transaction.note_ciphertext = note_ciphertext;
+    transaction.parent_tx_id = parent_tx_id;
+    transaction.user_seq = user_seq;
+    transaction.retry_count = 0;
+    // Write-once; nothing updates it after creation
+    transaction.external_ref = external_ref;
+    transaction.priority = priority;
+    transaction.client_timestamp = client_timestamp;
+    transaction.risk_nonce = risk_nonce;
+    transaction.quoted_rate = quoted_rate;
+    transaction.refunded_amount = 0;
+    transaction.accrued_volume = 0;
+    transaction.accrued_to_micro = false;
+    transaction.fail_grace_until = 0;
+    // A flagged transaction gets a nonce the approver must sign along
+    // with the tx_id; an all-zero nonce means no approval is needed
+    let flagged = config.approval_threshold > 0 && amount_ngn > config.approval_threshold;
+    transaction.approval_nonce = if flagged {
+        hashv(&[&tx_id, &user_phone, &transaction.timestamp.to_le_bytes()]).to_bytes()
+    } else {
+        [0; 32]
+    };
+    transaction.approved = false;
+    user_account.hold_pending_usdc(transaction)?;
+    
+    if config.emits_events_for(&transaction.tx_type) {
+        emit!(TransactionCreated {
+            tx_id,
+            user_phone,
+            tx_type: transaction.tx_type.clone(),
+            amount_ngn: stored_amount,
+            fee,
+            priority,
+            timestamp: transaction.timestamp,
+        });
+    }
+    Ok(())
+}
+
+// Checks shared by create_transaction and simulate_transaction. On success
+// returns the fee that would be charged.
+fn validate_transaction(
+    config: &Config,
+    user_account: &UserAccount,
+    terms: &TransactionTerms,
+    now: i64,
+) -> std::result::Result<FeeQuote, OuhError> {
+    let TransactionTerms {
+        ref tx_type,
+        amount_ngn,
+        amount_usdc,
+        tip_ngn,
+        priority,
+        client_timestamp,
+    } = *terms;
+    
+    // Check if contract is paused
+    if config.paused {
+        return Err(OuhError::ContractPaused);
+    }
+    
+    if config.new_tx_frozen {
+        return Err(OuhError::NewTransactionsFrozen);
+    }
+    
+    // A zero min_limit is valid config, but a zero amount never is
+    if amount_ngn == 0 {
+        return Err(OuhError::ZeroAmount);
+    }
+    
+    // Check transaction limits. Promo users get a lower floor, never a
+    // higher ceiling.
+    let promo_available = user_account.promo_enabled
+        && user_account.promo_tx_count < config.max_promo_transactions;
+    let min_limit = if promo_available {
+        config.promo_min_limit.min(config.effective_min_limit())
+    } else {
+        config.effective_min_limit()
+    };
+    if amount_ngn < min_limit || amount_ngn > config.max_limit_for(user_account.loyalty_level) {
+        return Err(OuhError::TransactionLimitOutOfBounds);
+    }
+    
+    // Suspension stops purchases; with allow_suspended_withdrawal the user
+    // can still cash out their own balance
+    match user_account.status {
+        UserStatus::Active => {}
+        UserStatus::Suspended
+            if config.allow_suspended_withdrawal
+                && *tx_type == TransactionType::Withdrawal => {}
+        UserStatus::Suspended => return Err(OuhError::UserSuspended),
+        UserStatus::Banned => return Err(OuhError::UserBanned),
+    }
+    
+    if !config.is_within_business_hours(tx_type, now) {
+        return Err(OuhError::OutsideBusinessHours);
+    }
+    
+    if *tx_type == TransactionType::Airtime
+        && !config.is_allowed_airtime_denomination(amount_ngn)
+    {
+        return Err(OuhError::InvalidDenomination);
+    }
+    
+    if config.max_amount_digits > 0
+        && significant_digits(amount_ngn) > config.max_amount_digits as u32
+    {
+        return Err(OuhError::AmountTooPrecise);
+    }
+    
+    // Reject sudden spikes against the user's running average once there
+    // is enough history to judge
+    let has_history = user_account.amount_samples > 0
+        && user_account.amount_samples >= config.velocity_min_history;
+    if config.velocity_multiplier > 0 && has_history {
+        let ceiling = (user_account.avg_amount as u128)
+            * (config.velocity_multiplier as u128);
+        if (amount_ngn as u128) > ceiling {
+            return Err(OuhError::VelocityExceeded);
+        }
+    }
+    
+    // A fork can step the clock back; a user's records must never go
+    // backwards in time, where safe_elapsed would only see a zero gap
+    if now < user_account.last_tx_timestamp {
+        return Err(OuhError::NonMonotonicTime);
+    }
+    
+    // Crypto purchases must carry a USDC leg, nothing else does
+    let usdc_valid = match tx_type {
+        TransactionType::Crypto => matches!(amount_usdc, Some(amount) if amount > 0),
+        TransactionType::Airtime | TransactionType::Withdrawal => amount_usdc.is_none(),
+    };
+    if !usdc_valid {
+        return Err(OuhError::InvalidUsdcForType);
+    }
+    
+    // Reject dust that doesn't line up with the NGN leg
+    if let Some(amount) = amount_usdc {
+        let aligned = config.usdc_min_unit == 0 || amount % config.usdc_min_unit == 0;
+        if amount > MAX_AMOUNT_USDC || !aligned {
+            return Err(OuhError::InvalidPrecision);
+        }
+        
+        // Pending crypto transactions count too, or each could pass alone
+        let exposure = user_account.total_usdc_volume
+            .saturating_add(user_account.pending_usdc)
+            .saturating_add(amount);
+        if config.max_user_usdc_exposure > 0 && exposure > config.max_user_usdc_exposure {
+            return Err(OuhError::UsdcExposureExceeded);
+        }
+    }
+    
+    // The device's own clock, kept for analytics once it's close to ours
+    if let Some(client_timestamp) = client_timestamp {
+        require_within_skew(config, client_timestamp, now)?;
+    }
+    
+    // Scheduling hint for the off-chain keeper; the program never reorders
+    if priority > MAX_PRIORITY {
+        return Err(OuhError::InvalidPriority);
+    }
+    
+    // Tips are a separate flow from the fee, paid out to tip_recipient
+    if tip_ngn > config.max_tip_ngn {
+        return Err(OuhError::TipTooLarge);
+    }
+    
+    // Promo transactions below the normal minimum, a new user's first
+    // transactions and micro airtime top-ups are fee-free. Otherwise the fee
+    // is computed on-chain from the live bps, and the bps used is stored with
+    // the transaction so the record is self-describing.
+    let is_promo = amount_ngn < config.effective_min_limit();
+    let is_micro_airtime = *tx_type == TransactionType::Airtime
+        && amount_ngn < config.airtime_fee_free_below;
+    let fee_waived = is_promo || is_micro_airtime || user_account.free_tx_remaining > 0;
+    let fee_bps = if fee_waived { 0 } else { config.fee_bps_for(tx_type) };
+    let fee = if fee_bps > 0 {
+        config.bound_fee(compute_fee(amount_ngn, fee_bps))
+    } else {
+        0
+    };
+    if fee > amount_ngn {
+        return Err(OuhError::FeeExceedsAmount);
+    }
+    // Catches a fee floor that's steep for a small amount
+    let fee_ratio_bps = (fee as u128) * (BPS_DENOMINATOR as u128) / (amount_ngn as u128);
+    if config.max_fee_ratio_bps > 0 && fee_ratio_bps > config.max_fee_ratio_bps as u128 {
+        return Err(OuhError::FeeTooHigh);
+    }
+    Ok(FeeQuote {
+        is_promo,
+        fee_bps,
+        fee,
+    })
+}
+
+// Deserializes a transaction passed through remaining_accounts
+fn load_transaction(info: &AccountInfo) -> Result<TransactionAccount> {
+    if info.owner != &crate::ID {
+        return Err(ErrorCode::AccountOwnedByWrongProgram.into());
+    }
+    let data = info.try_borrow_data()?;
+    TransactionAccount::try_deserialize(&mut &data[..])
+}
+
+fn load_user(info: &AccountInfo) -> Result<UserAccount> {
+    if info.owner != &crate::ID {
+        return Err(ErrorCode::AccountOwnedByWrongProgram.into());
+    }
+    let data = info.try_borrow_data()?;
+    UserAccount::try_deserialize(&mut &data[..])
+}
+
+fn close_account<'info>(
+    info: &AccountInfo<'info>,
+    destination: &AccountInfo<'info>,
+) -> Result<()> {
+    let lamports = info.lamports();
+    **destination.try_borrow_mut_lamports()? = destination.lamports()
+        .checked_add(lamports)
+        .ok_or(OuhError::ArithmeticOverflow)?;
+    **info.try_borrow_mut_lamports()? = 0;
+    
+    info.assign(&system_program::ID);
+    info.resize(0)?;
+    Ok(())
+}
+
+// Rounds to the nearest multiple of bucket_size, never down to zero.
+// A zero bucket_size leaves the amount as-is.
+pub fn bucket_amount(amount_ngn: u64, bucket_size: u64) -> u64 {
+    if bucket_size == 0 {
+        return amount_ngn;
+    }
+    let buckets = (amount_ngn.saturating_add(bucket_size / 2) / bucket_size).max(1);
+    buckets.saturating_mul(bucket_size)
+}
+
+// Fee rounds down, so the user is never charged more than the stated bps
+pub fn compute_fee(amount_ngn: u64, fee_bps: u16) -> u64 {
+    let fee = (amount_ngn as u128) * (fee_bps as u128) / (BPS_DENOMINATOR as u128);
+    fee as u64
+}
+
+// Marks the transaction Failed. The held amount and tip go back to the user;
+// the fee stays taken less any failure rebate.
+fn fail_transaction_funds(
+    transaction: &mut TransactionAccount,
+    user_account: &mut UserAccount,
+    config: &mut Config,
+) -> Result<()> {
+    transaction.status = TransactionStatus::Failed;
+    user_account.release_pending_usdc(transaction);
+    
+    let released = transaction.debited_amount
+        .checked_sub(transaction.held_fee)
+        .ok_or(OuhError::ArithmeticOverflow)?;
+    user_account.available_balance = user_account.available_balance
+        .checked_add(released)
+        .ok_or(OuhError::ArithmeticOverflow)?;
+    transaction.debited_amount = transaction.held_fee;
+    
+    // Part of the fee goes back to the user, once per transaction however
+    // often it fails. A held fee is refunded to the balance; otherwise the
+    // rebate is recorded as owed.
+    let rebate = compute_fee(transaction.fee, config.failure_rebate_bps)
+        .saturating_sub(transaction.fee_rebate);
+    if rebate > 0 {
+        transaction.fee_rebate = transaction.fee_rebate
+            .checked_add(rebate)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        if transaction.held_fee >= rebate {
+            transaction.held_fee = transaction.held_fee
+                .checked_sub(rebate)
+                .ok_or(OuhError::ArithmeticOverflow)?;
+            transaction.debited_amount = transaction.held_fee;
+            user_account.available_balance = user_account.available_balance
+                .checked_add(rebate)
+                .ok_or(OuhError::ArithmeticOverflow)?;
+        } else {
+            user_account.rebate_owed = user_account.rebate_owed
+                .checked_add(rebate)
+                .ok_or(OuhError::ArithmeticOverflow)?;
+        }
+    }
+    
+    config.total_tips = config.total_tips.saturating_sub(transaction.tip_ngn);
+    Ok(())
+}
+
+// Takes a failed transaction's amount and tip again, as at creation. The
+// exact held amount goes back on hold, not the bucketed amount_ngn, and the
+// rebate is taken back: it was for a failure that no longer stands.
+fn retake_transaction_funds(
+    transaction: &mut TransactionAccount,
+    user_account: &mut UserAccount,
+    config: &mut Config,
+) -> Result<()> {
+    if transaction.held_amount > 0 {
+        let held = transaction.held_amount
+            .checked_add(transaction.tip_ngn)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        user_account.available_balance = user_account.available_balance
+            .checked_sub(held)
+            .ok_or(OuhError::InsufficientBalance)?;
+        transaction.debited_amount = transaction.debited_amount
+            .checked_add(held)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+    }
+    config.total_tips = config.total_tips
+        .checked_add(transaction.tip_ngn)
+        .ok_or(OuhError::ArithmeticOverflow)?;
+    user_account.hold_pending_usdc(transaction)?;
+    reverse_fee_rebate(transaction, user_account)
+}
+
+// Takes back the failure rebate. fail_transaction_funds paid it to the
+// balance when the fee was held, and recorded it owed otherwise.
+fn reverse_fee_rebate(
+    transaction: &mut TransactionAccount,
+    user_account: &mut UserAccount,
+) -> Result<()> {
+    let rebate = transaction.fee_rebate;
+    if rebate == 0 {
+        return Ok(());
+    }
+    if transaction.held_amount > 0 {
+        user_account.available_balance = user_account.available_balance
+            .checked_sub(rebate)
+            .ok_or(OuhError::InsufficientBalance)?;
+        transaction.held_fee = transaction.held_fee
+            .checked_add(rebate)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+        transaction.debited_amount = transaction.debited_amount
+            .checked_add(rebate)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+    } else {
+        user_account.rebate_owed = user_account.rebate_owed.saturating_sub(rebate);
+    }
+    transaction.fee_rebate = 0;
+    Ok(())
+}
+
+// The referrer's cut of a completed transaction's fee. Whenever the user was
+// referred, the referrer's PDA must be passed so the share can't be skipped;
+// a referrer who has since closed their account forfeits it.
+fn credit_referrer(
+    referrer_account: Option<&AccountInfo>,
+    user_account: &UserAccount,
+    fee: u64,
+    config: &Config,
+) -> Result<()> {
+    let Some(referrer_phone) = user_account.referrer_phone else {
+        return Ok(());
+    };
+    let Some(info) = referrer_account else {
+        return Err(OuhError::ReferrerAccountRequired.into());
+    };
+    let (expected, _) = Pubkey::find_program_address(&[USER_SEED, &referrer_phone], &crate::ID);
+    if info.key() != expected {
+        return Err(OuhError::InvalidReferrer.into());
+    }
+    if info.owner != &crate::ID || info.data_is_empty() {
+        return Ok(());
+    }
+    
+    let mut referrer = load_user(info)?;
+    let share = compute_fee(fee, config.referrer_fee_share_bps);
+    referrer.referral_rewards = referrer.referral_rewards
+        .checked_add(share)
+        .ok_or(OuhError::ArithmeticOverflow)?;
+    referrer.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+    Ok(())
+}
+
+fn record_daily_stats(daily_stats: &mut DailyStats, amount_ngn: u64, now: i64) -> Result<()> {
+    daily_stats.day = utc_day(now);
+    daily_stats.total_volume = daily_stats.total_volume
+        .checked_add(amount_ngn)
+        .ok_or(OuhError::ArithmeticOverflow)?;
+    daily_stats.tx_count = daily_stats.tx_count.checked_add(1).ok_or(OuhError::ArithmeticOverflow)?;
+    Ok(())
+}
+
+fn settle_transaction(
+    transaction: &mut TransactionAccount,
+    user_account: &mut UserAccount,
+    config: &Config,
+) -> Result<()> {
+    transaction.status = TransactionStatus::Completed;
+    
+    // Update user's total volume. Dust accrues separately so it can't push
+    // total_volume across tier thresholds.
+    let accrued = config.accrued_volume(transaction.amount_ngn, transaction.fee);
+    transaction.accrued_volume = accrued;
+    transaction.accrued_to_micro = transaction.amount_ngn < config.min_volume_accrual;
+    if transaction.accrued_to_micro {
+        user_account.micro_volume = user_account.micro_volume
+            .checked_add(accrued)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+    } else {
+        user_account.total_volume = user_account.total_volume
+            .checked_add(accrued)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+    }
+    
+    user_account.free_tx_remaining = user_account.free_tx_remaining.saturating_sub(1);
+    
+    // Tamper-evidence: any mirror replaying the user's completions in order
+    // must arrive at the same hash
+    user_account.tx_chain_hash = keccak::hashv(&[
+        &user_account.tx_chain_hash,
+        &transaction.tx_id,
+        &transaction.amount_ngn.to_le_bytes(),
+        &transaction.timestamp.to_le_bytes(),
+    ])
+    .to_bytes();
+    
+    // Levels only go up; raising thresholds later leaves users where they are
+    let level = config.loyalty_level_for(user_account.total_volume);
+    if level > user_account.loyalty_level {
+        user_account.loyalty_level = level;
+        emit!(TierUpgraded {
+            user_phone: user_account.phone_number,
+            loyalty_level: level,
+            total_volume: user_account.total_volume,
+            timestamp: current_timestamp()?,
+        });
+    }
+    
+    // Tracked apart from total_volume; USDC carries a different risk profile
+    user_account.release_pending_usdc(transaction);
+    if let Some(amount_usdc) = transaction.amount_usdc {
+        user_account.total_usdc_volume = user_account.total_usdc_volume
+            .checked_add(amount_usdc)
+            .ok_or(OuhError::ArithmeticOverflow)?;
+    }
+    
+    if config.emits_events_for(&transaction.tx_type) {
+        emit!(TransactionCompleted {
+            tx_id: transaction.tx_id,
+            user_phone: transaction.user_phone,
+            amount_ngn: transaction.amount_ngn,
+            notifications_enabled: user_account.notifications_enabled,
+            timestamp: current_timestamp()?,
+        });
+    }
+    Ok(())
+}
+
+// Context Structs
+#[derive(Accounts)]
+#[instruction(
+    crypto_fee_bps: u16,
+    airtime_fee_bps: u16,
+    min_limit: u64,
+    max_limit: u64,
+    env_id: u8
+)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = admin,
+        space = Config::LEN,
+        seeds = [CONFIG_SEED, config_env_seed(&env_id)],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = admin,
+        space = ConfigSnapshot::LEN,
+        seeds = [
+            CONFIG_SNAPSHOT_SEED,
+            config_env_seed(&env_id),
+            &0u64.to_le_bytes()
+        ],
+        bump
+    )]
+    pub config_snapshot: Account<'info, ConfigSnapshot>,
+    // Required for any env_id but the default
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump
+    )]
+    pub default_config: Option<Account<'info, Config>>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(env_id: u8)]
+pub struct MigrateConfig<'info> {
+    /// CHECK: may predate the current layout; discriminator and admin are checked by hand
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [CONFIG_SEED, config_env_seed(&env_id)],
+        bump
+    )]
+    pub config: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = admin @ OuhError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimByBackup<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = backup_admin @ OuhError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    pub backup_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeConfig<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        init,
+        payer = authority,
+        space = ConfigSnapshot::LEN,
+        seeds = [
+            CONFIG_SNAPSHOT_SEED,
+            config.env_seed(),
+            &(config.config_epoch + 1).to_le_bytes()
+        ],
+        bump
+    )]
+    pub config_snapshot: Account<'info, ConfigSnapshot>,
+    #[account(
+        seeds = [ROLE_SEED, config.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DelegatedConfigUpdate<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [ROLE_SEED, config.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(grantee: Pubkey)]
+pub struct GrantRole<'info> {
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = Role::LEN,
+        seeds = [ROLE_SEED, config.key().as_ref(), grantee.as_ref()],
+        bump
+    )]
+    pub role: Account<'info, Role>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = admin @ OuhError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeRole<'info> {
+    #[account(
+        mut,
+        seeds = [ROLE_SEED, config.key().as_ref(), role.grantee.as_ref()],
+        bump,
+        close = admin
+    )]
+    pub role: Account<'info, Role>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = admin @ OuhError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(phone_number: [u8; 14])]
+pub struct RegisterUser<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = UserAccount::LEN,
+        seeds = [USER_SEED, &phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        mut,
+        seeds = [ARCHIVE_SEED, &phone_hash(&phone_number)],
+        bump
+    )]
+    pub user_archive: Option<Account<'info, UserArchive>>,
+    #[account(
+        init,
+        payer = user,
+        space = WalletIndex::LEN,
+        seeds = [WALLET_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub wallet_index: Option<Account<'info, WalletIndex>>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(phone_number: [u8; 14], pin_hash: [u8; 32], tx_id: [u8; 16])]
+pub struct RegisterAndTransact<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = UserAccount::LEN,
+        seeds = [USER_SEED, &phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        init,
+        payer = user,
+        space = TransactionAccount::LEN,
+        seeds = [TRANSACTION_SEED, &tx_id],
+        bump
+    )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+    #[account(
+        mut,
+        seeds = [RESERVATION_SEED, &tx_id],
+        bump,
+        close = user,
+        constraint = reservation.owner == user.key() @ OuhError::Unauthorized
+    )]
+    pub reservation: Account<'info, TxReservation>,
+    #[account(
+        init,
+        payer = user,
+        space = WalletIndex::LEN,
+        seeds = [WALLET_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub wallet_index: Option<Account<'info, WalletIndex>>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseUser<'info> {
+    #[account(
+        mut,
+        close = user,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserArchive::LEN,
+        seeds = [ARCHIVE_SEED, &phone_hash(&user_account.phone_number)],
+        bump
+    )]
+    pub user_archive: Option<Account<'info, UserArchive>>,
+    #[account(
+        mut,
+        close = user,
+        seeds = [WALLET_SEED, user.key().as_ref()],
+        bump,
+        constraint = wallet_index.phone_number == user_account.phone_number
+            @ OuhError::Unauthorized
+    )]
+    pub wallet_index: Option<Account<'info, WalletIndex>>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateOwnUser<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetReferrer<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [USER_SEED, &referrer_account.phone_number],
+        bump
+    )]
+    pub referrer_account: Account<'info, UserAccount>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetCategoryLimit<'info> {
+    #[account(
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserBudget::LEN,
+        seeds = [BUDGET_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_budget: Account<'info, UserBudget>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ApproveRecovery<'info> {
+    #[account(
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        init_if_needed,
+        payer = guardian,
+        space = RecoveryRequest::LEN,
+        seeds = [RECOVERY_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub recovery_request: Account<'info, RecoveryRequest>,
+    #[account(mut)]
+    pub guardian: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RecoverViaGuardians<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        mut,
+        close = new_wallet,
+        seeds = [RECOVERY_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub recovery_request: Account<'info, RecoveryRequest>,
+    /// CHECK: the old wallet's index, if it has one; the handler reads it
+    /// only when this program owns it
+    #[account(
+        mut,
+        seeds = [WALLET_SEED, user_account.wallet.as_ref()],
+        bump
+    )]
+    pub old_wallet_index: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = new_wallet,
+        space = WalletIndex::LEN,
+        seeds = [WALLET_SEED, new_wallet.key().as_ref()],
+        bump
+    )]
+    pub new_wallet_index: Option<Account<'info, WalletIndex>>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub new_wallet: Signer<'info>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ChangePin<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct LinkTokenAccount<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateUser<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = admin @ OuhError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReconcileUser<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = admin @ OuhError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct TransferVolume<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &from_user.phone_number],
+        bump
+    )]
+    pub from_user: Account<'info, UserAccount>,
+    #[account(
+        mut,
+        seeds = [USER_SEED, &to_user.phone_number],
+        bump,
+        constraint = to_user.key() != from_user.key() @ OuhError::SameUserAccount
+    )]
+    pub to_user: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = admin @ OuhError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct DelegatedUserUpdate<'info> {
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [ROLE_SEED, config.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(tx_id: [u8; 16])]
+pub struct ReserveTxId<'info> {
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = TxReservation::LEN,
+        seeds = [RESERVATION_SEED, &tx_id],
+        bump
+    )]
+    pub reservation: Account<'info, TxReservation>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    /// CHECK: repaid the rent when an expired reservation is taken over; the
+    /// handler checks it is the reservation's owner
+    #[account(mut)]
+    pub previous_owner: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReclaimReservation<'info> {
+    #[account(
+        mut,
+        seeds = [RESERVATION_SEED, &reservation.tx_id],
+        bump,
+        close = owner,
+        has_one = owner @ OuhError::Unauthorized
+    )]
+    pub reservation: Account<'info, TxReservation>,
+    /// CHECK: receives the rent; must be the reservation's owner
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(tx_id: [u8; 16], user_phone: [u8; 14])]
+pub struct CreateTransaction<'info> {
+    #[account(
+        init,
+        payer = user,
+        space = TransactionAccount::LEN,
+        seeds = [TRANSACTION_SEED, &tx_id],
+        bump
+    )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+    #[account(
+        seeds = [TRANSACTION_SEED, &parent_transaction.tx_id],
+        bump
+    )]
+    pub parent_transaction: Option<Account<'info, TransactionAccount>>,
+    /// CHECK: external merchant order; only its owner is validated
+    pub external_ref: Option<UncheckedAccount<'info>>,
+    #[account(
+        mut,
+        seeds = [RESERVATION_SEED, &tx_id],
+        bump,
+        close = user,
+        constraint = reservation.owner == user.key() @ OuhError::Unauthorized
+    )]
+    pub reservation: Account<'info, TxReservation>,
+    #[account(
+        mut,
+        seeds = [USER_SEED, &user_phone],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        mut,
+        seeds = [BUDGET_SEED, &user_phone],
+        bump
+    )]
+    pub user_budget: Option<Account<'info, UserBudget>>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(mut)]
+    pub user: Signer<'info>,
+    pub guardian: Option<Signer<'info>>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SimulateTransaction<'info> {
+    #[account(
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct CompleteTransaction<'info> {
+    #[account(
+        mut,
+        seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
+        bump
+    )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+    #[account(
+        mut,
+        seeds = [USER_SEED, &transaction_account.user_phone],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [ROLE_SEED, config.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+    /// CHECK: the referrer's user PDA, checked and written by credit_referrer
+    #[account(mut)]
+    pub referrer_account: Option<UncheckedAccount<'info>>,
+    // Keyed by the day on the chain clock, so a client racing midnight may
+    // need to retry against the next day's PDA
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = DailyStats::LEN,
+        seeds = [DAILY_STATS_SEED, &utc_day(current_timestamp()?).to_le_bytes()],
+        bump
+    )]
+    pub daily_stats: Account<'info, DailyStats>,
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    pub system_program: Program<'info, System>,
 }
 
-// PDA Seeds
-pub const USER_SEED: &[u8] = b"user";
-pub const TRANSACTION_SEED: &[u8] = b"transaction";
-pub const CONFIG_SEED: &[u8] = b"config";
-
-// Account Structures
-#[account]
-pub struct UserAccount {
-    pub phone_number: [u8; 14],
-    pub wallet: Pubkey,
-    pub pin_hash: [u8; 32],
-    pub total_volume: u64,
-    pub registered_at: i64,
-    pub status: UserStatus,
-}
-
-#[account]
-pub struct TransactionAccount {
-    pub tx_id: [u8; 16],
-    pub user_phone: [u8; 14],
-    pub tx_type: TransactionType,
-    pub amount_ngn: u64,
-    pub amount_usdc: Option<u64>,
-    pub status: TransactionStatus,
-    pub timestamp: i64,
-    pub fee: u64,
-}
-
-#[account]
-pub struct Config {
-    pub admin: Pubkey,
-    pub crypto_fee_bps: u16,
-    pub airtime_fee_bps: u16,
-    pub min_limit: u64,
-    pub max_limit: u64,
-    pub paused: bool,
-}
-
-// Enums
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum UserStatus {
-    Active,
-    Suspended,
-}
-
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum TransactionType {
-    Crypto,
-    Airtime,
-}
-
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum TransactionStatus {
-    Pending,
-    Completed,
-    Failed,
-}
-
-// Account Size Implementations
-impl UserAccount {
-    pub const LEN: usize = 8 + 14 + 32 + 32 + 8 + 8 + 1; //103 bytes
-}
-
-impl TransactionAccount {
-    pub const LEN: usize = 8 + 16 + 14 + 1 + 8 + 9 + 1 + 8 + 8;
+#[derive(Accounts)]
+pub struct ProvideApproval<'info> {
+    #[account(
+        mut,
+        seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
+        bump
+    )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    /// CHECK: address-constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
 }
 
-impl Config {
-    pub const LEN: usize = 8 + 32 + 2 + 2 + 8 + 8 + 1;
+#[derive(Accounts)]
+pub struct UpdateTransactionStatus<'info> {
+    #[account(
+        mut,
+        seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
+        bump
+    )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+    #[account(
+        mut,
+        seeds = [USER_SEED, &transaction_account.user_phone],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    #[account(
+        seeds = [ROLE_SEED, config.key().as_ref(), authority.key().as_ref()],
+        bump
+    )]
+    pub role: Option<Account<'info, Role>>,
+    pub authority: Signer<'info>,
 }
 
-// Context Structs
 #[derive(Accounts)]
-pub struct InitializeConfig<'info> {
+pub struct ForceComplete<'info> {
     #[account(
-        init,
-        payer = admin,
-        space = Config::LEN,
-        seeds = [CONFIG_SEED],
+        mut,
+        seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
+        bump
+    )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+    #[account(
+        mut,
+        seeds = [USER_SEED, &transaction_account.user_phone],
         bump
     )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = admin @ OuhError::Unauthorized
+    )]
     pub config: Account<'info, Config>,
+    /// CHECK: the referrer's user PDA, checked and written by credit_referrer
+    #[account(mut)]
+    pub referrer_account: Option<UncheckedAccount<'info>>,
+    #[account(
+        init_if_needed,
+        payer = admin,
+        space = DailyStats::LEN,
+        seeds = [DAILY_STATS_SEED, &utc_day(current_timestamp()?).to_le_bytes()],
+        bump
+    )]
+    pub daily_stats: Account<'info, DailyStats>,
     #[account(mut)]
     pub admin: Signer<'info>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(phone_number: [u8; 14])]
-pub struct RegisterUser<'info> {
+pub struct CancelTransaction<'info> {
     #[account(
-        init,
-        payer = user,
-        space = UserAccount::LEN,
-        seeds = [USER_SEED, &phone_number],
+        mut,
+        seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
         bump
     )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+    #[account(
+        mut,
+        seeds = [USER_SEED, &transaction_account.user_phone],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
     pub user_account: Account<'info, UserAccount>,
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
     pub user: Signer<'info>,
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(tx_id: [u8; 16], user_phone: [u8; 14])]
-pub struct CreateTransaction<'info> {
+pub struct DisputeTransaction<'info> {
     #[account(
-        init,
-        payer = user,
-        space = TransactionAccount::LEN,
-        seeds = [TRANSACTION_SEED, &tx_id],
+        mut,
+        seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
         bump
     )]
     pub transaction_account: Account<'info, TransactionAccount>,
     #[account(
         mut,
-        seeds = [USER_SEED, &user_phone],
-        bump
+        seeds = [USER_SEED, &transaction_account.user_phone],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
     )]
     pub user_account: Account<'info, UserAccount>,
     #[account(
-        seeds = [CONFIG_SEED],
+        seeds = [CONFIG_SEED, config.env_seed()],
         bump
     )]
     pub config: Account<'info, Config>,
-    #[account(mut)]
     pub user: Signer<'info>,
-    pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct CompleteTransaction<'info> {
+pub struct ResolveDispute<'info> {
     #[account(
         mut,
         seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
@@ -244,8 +4818,70 @@ pub struct CompleteTransaction<'info> {
         bump
     )]
     pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump,
+        has_one = admin @ OuhError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    pub admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ArchiveTransaction<'info> {
+    #[account(
+        mut,
+        seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
+        bump
+    )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+    #[account(
+        seeds = [USER_SEED, &transaction_account.user_phone],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseTransactionsBatch<'info> {
+    #[account(
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump,
+        constraint = user_account.wallet == user.key() @ OuhError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
     #[account(mut)]
-    pub authority: Signer<'info>,
+    pub user: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct GetUserTxPage<'info> {
+    #[account(
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct GetTransactionChain<'info> {
+    #[account(
+        seeds = [TRANSACTION_SEED, &transaction_account.tx_id],
+        bump
+    )]
+    pub transaction_account: Account<'info, TransactionAccount>,
+}
+
+#[derive(Accounts)]
+#[instruction(day: u32)]
+pub struct GetDailyStats<'info> {
+    #[account(
+        seeds = [DAILY_STATS_SEED, &day.to_le_bytes()],
+        bump
+    )]
+    pub daily_stats: Account<'info, DailyStats>,
 }
 
 #[derive(Accounts)]
@@ -258,6 +4894,126 @@ pub struct GetUserBalance<'info> {
     pub user_account: Account<'info, UserAccount>,
 }
 
+#[derive(Accounts)]
+pub struct AttestVolume<'info> {
+    #[account(
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct GetUserStats<'info> {
+    #[account(
+        seeds = [USER_SEED, &user_account.phone_number],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ReadConfig<'info> {
+    #[account(
+        seeds = [CONFIG_SEED, config.env_seed()],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct CheckTxId {}
+
+#[derive(Accounts)]
+pub struct GetBalancesBatch {}
+
+#[derive(Accounts)]
+pub struct RecommendComputeUnits {}
+
+// Events
+#[event]
+pub struct ForceCompleted {
+    pub tx_id: [u8; 16],
+    pub admin: Pubkey,
+    pub override_reason: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TransactionCreated {
+    pub tx_id: [u8; 16],
+    pub user_phone: [u8; 14],
+    pub tx_type: TransactionType,
+    pub amount_ngn: u64,
+    pub fee: u64,
+    pub priority: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TierUpgraded {
+    pub user_phone: [u8; 14],
+    pub loyalty_level: u8,
+    pub total_volume: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TransactionCompleted {
+    pub tx_id: [u8; 16],
+    pub user_phone: [u8; 14],
+    pub amount_ngn: u64,
+    pub notifications_enabled: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserReconciled {
+    pub phone: [u8; 14],
+    pub volume_delta: i64,
+    pub micro_volume_delta: i64,
+    pub usdc_volume_delta: i64,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct VolumeTransferred {
+    pub from_phone: [u8; 14],
+    pub to_phone: [u8; 14],
+    pub amount: u64,
+    pub admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TransactionRetried {
+    pub tx_id: [u8; 16],
+    pub retry_count: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TransactionDisputed {
+    pub tx_id: [u8; 16],
+    pub user_phone: [u8; 14],
+    pub recent_dispute_count: u8,
+    pub suspended: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct TransactionApproved {
+    pub tx_id: [u8; 16],
+    pub approver: Pubkey,
+    pub timestamp: i64,
+}
+
 // Error Codes
 #[error_code]
 pub enum OuhError {
@@ -273,4 +5029,267 @@ pub enum OuhError {
     InsufficientBalance,
     #[msg("Invalid PIN")]
     InvalidPin,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Invalid transaction status")]
+    InvalidTransactionStatus,
+    #[msg("USDC amount is required for crypto and forbidden for airtime")]
+    InvalidUsdcForType,
+    #[msg("Airtime amount is not an allowed denomination")]
+    InvalidDenomination,
+    #[msg("New transactions are frozen for maintenance")]
+    NewTransactionsFrozen,
+    #[msg("Registration cap reached")]
+    RegistrationCapReached,
+    #[msg("Tip exceeds the configured maximum")]
+    TipTooLarge,
+    #[msg("Transaction id is reserved by another wallet")]
+    TxIdReserved,
+    #[msg("Transaction id reservation has expired")]
+    ReservationExpired,
+    #[msg("Transaction amount is far above the user's recent average")]
+    VelocityExceeded,
+    #[msg("Invalid config parameters")]
+    InvalidConfig,
+    #[msg("Parent transaction is missing, mismatched or not settled")]
+    InvalidParentTransaction,
+    #[msg("Too many accounts in batch")]
+    BatchTooLarge,
+    #[msg("USDC amount is out of bounds or not aligned to the minimum unit")]
+    InvalidPrecision,
+    #[msg("Transaction type not allowed outside business hours")]
+    OutsideBusinessHours,
+    #[msg("Transaction has reached its retry limit")]
+    RetryLimitReached,
+    #[msg("User still has an available balance")]
+    BalanceNotEmpty,
+    #[msg("Transaction amount must be greater than zero")]
+    ZeroAmount,
+    #[msg("Fee exceeds the transaction amount")]
+    FeeExceedsAmount,
+    #[msg("External reference is not owned by the partner program")]
+    InvalidExternalRef,
+    #[msg("Transaction would exceed the user's USDC exposure cap")]
+    UsdcExposureExceeded,
+    #[msg("User is permanently banned")]
+    UserBanned,
+    #[msg("No max_limit increase is pending")]
+    NoPendingLimitIncrease,
+    #[msg("max_limit increase is still timelocked")]
+    LimitIncreaseLocked,
+    #[msg("Priority is out of range")]
+    InvalidPriority,
+    #[msg("Transfer exceeds the source user's volume")]
+    VolumeUnderflow,
+    #[msg("Source and destination must be different users")]
+    SameUserAccount,
+    #[msg("Admin has been active within the takeover timeout")]
+    AdminStillActive,
+    #[msg("Token account is not the wallet's associated token account for the USDC mint")]
+    InvalidTokenAccount,
+    #[msg("Transaction requires the user's guardian to co-sign")]
+    GuardianSignatureRequired,
+    #[msg("Account is not the transaction PDA for this id")]
+    InvalidTransactionAccount,
+    #[msg("Cluster clock reported a non-positive timestamp")]
+    InvalidClock,
+    #[msg("Transaction is awaiting risk approval")]
+    ApprovalRequired,
+    #[msg("Missing or mismatched Ed25519 signature")]
+    InvalidSignature,
+    #[msg("Weekly limit for this spending category exceeded")]
+    CategoryLimitExceeded,
+    #[msg("No free spending category slot")]
+    CategorySlotsFull,
+    #[msg("Config is already at the current layout version")]
+    ConfigAlreadyMigrated,
+    #[msg("Reservation has not expired yet")]
+    ReservationStillActive,
+    #[msg("PIN was changed too recently")]
+    PinChangeTooSoon,
+    #[msg("Client timestamp is too far from the cluster clock")]
+    TimestampSkew,
+    #[msg("Display name must be printable UTF-8")]
+    InvalidDisplayName,
+    #[msg("Transaction was created under config terms that are no longer honored")]
+    EpochExpired,
+    #[msg("Clock is earlier than the user's last transaction")]
+    NonMonotonicTime,
+    #[msg("Risk nonce is missing or not signed by the risk engine")]
+    RiskCheckFailed,
+    #[msg("preserve_on_close is on; pass the user archive account")]
+    UserArchiveRequired,
+    #[msg("A referrer is already set for this user")]
+    ReferrerAlreadySet,
+    #[msg("Invalid referrer")]
+    InvalidReferrer,
+    #[msg("unique_wallets is on; pass the wallet index account")]
+    WalletIndexRequired,
+    #[msg("Not enough compute left to finish the batch")]
+    InsufficientCompute,
+    #[msg("Price quote has expired")]
+    QuoteExpired,
+    #[msg("Invalid price quote")]
+    InvalidQuote,
+    #[msg("Invalid recovery guardians")]
+    InvalidGuardians,
+    #[msg("Not enough guardians have approved the recovery")]
+    RecoveryThresholdNotMet,
+    #[msg("Recovery request does not match")]
+    RecoveryMismatch,
+    #[msg("The same account was passed more than once")]
+    DuplicateAccount,
+    #[msg("Fee is too large a share of the amount")]
+    FeeTooHigh,
+    #[msg("Refund must be positive and can't exceed the amount")]
+    InvalidRefundAmount,
+    #[msg("Transaction has not been pending long enough to complete")]
+    SettlementTooSoon,
+    #[msg("Amount has more significant digits than allowed")]
+    AmountTooPrecise,
+    #[msg("User was referred; pass the referrer account")]
+    ReferrerAccountRequired,
+    #[msg("Reservation is held by another wallet; pass it to repay its rent")]
+    PreviousOwnerRequired,
+    #[msg("Value is out of range")]
+    ArithmeticOverflow,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const NOW: i64 = 1_700_000_000;
+    
+    // What migrate_config reads once an old account has been grown
+    fn zeroed_config(config_version: u8) -> Config {
+        let mut data = vec![0; Config::LEN];
+        data[..8].copy_from_slice(Config::DISCRIMINATOR);
+        let mut config = Config::try_deserialize(&mut &data[..]).unwrap();
+        config.config_version = config_version;
+        config
+    }
+    
+    #[test]
+    fn partial_refunds_return_the_whole_usdc_leg() {
+        let mut data = vec![0; TransactionAccount::LEN];
+        data[..8].copy_from_slice(TransactionAccount::DISCRIMINATOR);
+        let mut transaction = TransactionAccount::try_deserialize(&mut &data[..]).unwrap();
+        transaction.amount_ngn = 3;
+        transaction.amount_usdc = Some(10);
+        
+        let mut shares = Vec::new();
+        for refunded_amount in 1..=3 {
+            transaction.refunded_amount = refunded_amount;
+            shares.push(transaction.refunded_usdc());
+        }
+        assert_eq!(shares, [3, 6, 10]);
+    }
+    
+    #[test]
+    fn migration_defaults_the_limit_multiplier() {
+        let mut config = zeroed_config(7);
+        config.max_limit = 1_000;
+        config.apply_defaults_since(config.config_version, NOW);
+        assert_eq!(config.limit_multiplier_bps, BPS_DENOMINATOR as u16);
+        assert_eq!(config.effective_max_limit(), config.max_limit);
+        
+        // A config that already had the field keeps its value
+        let mut config = zeroed_config(8);
+        config.limit_multiplier_bps = 5_000;
+        config.apply_defaults_since(config.config_version, NOW);
+        assert_eq!(config.limit_multiplier_bps, 5_000);
+    }
+    
+    #[test]
+    fn migration_keeps_events_on() {
+        let mut config = zeroed_config(1);
+        config.apply_defaults_since(config.config_version, NOW);
+        assert!(config.emits_events_for(&TransactionType::Crypto));
+        assert!(config.emits_events_for(&TransactionType::Airtime));
+        
+        let mut config = zeroed_config(2);
+        config.apply_defaults_since(config.config_version, NOW);
+        assert!(!config.emits_events_for(&TransactionType::Crypto));
+    }
+    
+    #[test]
+    fn migration_defaults_skew_and_business_days() {
+        let mut config = zeroed_config(0);
+        config.apply_defaults_since(config.config_version, NOW);
+        assert_eq!(config.max_client_skew, DEFAULT_MAX_CLIENT_SKEW);
+        assert_eq!(config.business_days, ALL_WEEKDAYS);
+        
+        // An admin's weekday mask is left alone
+        let mut config = zeroed_config(0);
+        config.restricted_tx_types = TransactionType::Airtime.flag();
+        config.business_days = 0b0011_1110;
+        config.apply_defaults_since(config.config_version, NOW);
+        assert_eq!(config.business_days, 0b0011_1110);
+        
+        let mut config = zeroed_config(5);
+        config.apply_defaults_since(config.config_version, NOW);
+        assert_eq!(config.max_client_skew, 0);
+    }
+    
+    #[test]
+    fn migration_fills_fields_from_before_versioning() {
+        let mut config = zeroed_config(0);
+        config.admin = Pubkey::new_unique();
+        config.min_limit = 100;
+        config.apply_defaults_since(config.config_version, NOW);
+        assert_eq!(config.promo_min_limit, 100);
+        assert_eq!(config.crypto_settler, config.admin);
+        assert_eq!(config.airtime_settler, config.admin);
+        assert_eq!(config.tip_recipient, config.admin);
+        assert_eq!(config.initialized_at, NOW);
+        assert_eq!(config.admin_last_seen, NOW);
+        assert_eq!(&config.ngn_symbol, b"NGN\0");
+        assert_eq!((config.ngn_decimals, config.usdc_decimals), (2, 6));
+        
+        // Values an unversioned config already carries are kept
+        let mut config = zeroed_config(0);
+        let settler = Pubkey::new_unique();
+        config.crypto_settler = settler;
+        config.initialized_at = 42;
+        config.usdc_decimals = 9;
+        config.apply_defaults_since(config.config_version, NOW);
+        assert_eq!(config.crypto_settler, settler);
+        assert_eq!(config.initialized_at, 42);
+        assert_eq!(config.usdc_decimals, 9);
+        assert_eq!(config.ngn_symbol, [0; 4]);
+        
+        // A versioned config already had every one of them
+        let mut config = zeroed_config(1);
+        config.apply_defaults_since(config.config_version, NOW);
+        assert_eq!(config.tip_recipient, Pubkey::default());
+        assert_eq!(config.initialized_at, 0);
+    }
+    
+    #[test]
+    fn safe_elapsed_treats_a_backward_clock_as_no_time() {
+        assert_eq!(safe_elapsed(1_000, 400), 600);
+        assert_eq!(safe_elapsed(400, 1_000), 0);
+        assert_eq!(safe_elapsed(i64::MIN, i64::MAX), 0);
+    }
+    
+    #[test]
+    fn budget_week_survives_a_backward_clock() {
+        let mut budget = UserBudget {
+            phone_number: [0; 14],
+            limits: [CategoryLimit::default(); MAX_CATEGORY_LIMITS],
+        };
+        budget.limits[0] = CategoryLimit {
+            category: 1,
+            weekly_limit: 1_000,
+            spent: 800,
+            week_start: 10 * SECONDS_PER_WEEK,
+        };
+        
+        // A week "before" the week started is still the same week
+        let earlier = 9 * SECONDS_PER_WEEK;
+        assert!(budget.charge(1, 500, earlier).is_err());
+        assert_eq!(budget.limits[0].spent, 800);
+        assert_eq!(budget.limits[0].week_start, 10 * SECONDS_PER_WEEK);
+    }
 }
@@ -1,16 +1,4775 @@
 import * as anchor from "@coral-xyz/anchor";
-import { Program } from "@coral-xyz/anchor";
+import { Program, BN } from "@coral-xyz/anchor";
+import {
+  ComputeBudgetProgram,
+  Ed25519Program,
+  Keypair,
+  LAMPORTS_PER_SOL,
+  PublicKey,
+  SYSVAR_INSTRUCTIONS_PUBKEY,
+} from "@solana/web3.js";
+import { keccak_256 } from "@noble/hashes/sha3";
+import { expect } from "chai";
+import { createHash } from "crypto";
 import { Ouh } from "../target/types/ouh";
 
 describe("ouh", () => {
   // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
 
   const program = anchor.workspace.ouh as Program<Ouh>;
+  const admin = provider.wallet;
 
-  it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
-    console.log("Your transaction signature", tx);
+  const [configPda] = PublicKey.findProgramAddressSync(
+    [Buffer.from("config")],
+    program.programId
+  );
+
+  let phoneCounter = 0;
+  const nextPhone = () =>
+    Array.from(
+      Buffer.from(`+23480${(++phoneCounter).toString().padStart(8, "0")}`)
+    );
+  const newTxId = () =>
+    Array.from(Keypair.generate().publicKey.toBytes().slice(0, 16));
+
+  const userPda = (phone: number[]) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("user"), Buffer.from(phone)],
+      program.programId
+    )[0];
+  const txPda = (txId: number[]) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("transaction"), Buffer.from(txId)],
+      program.programId
+    )[0];
+  const rolePda = (grantee: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("role"), configPda.toBuffer(), grantee.toBuffer()],
+      program.programId
+    )[0];
+  const configSnapshotPda = (epoch: number) =>
+    PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("config_snapshot"),
+        new BN(epoch).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+  // update_config writes the snapshot for the epoch it opens
+  const nextConfigSnapshot = async () => {
+    const config = await program.account.config.fetch(configPda);
+    return configSnapshotPda(config.configEpoch.toNumber() + 1);
+  };
+  const budgetPda = (phone: number[]) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("budget"), Buffer.from(phone)],
+      program.programId
+    )[0];
+  const reservationPda = (txId: number[]) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("reservation"), Buffer.from(txId)],
+      program.programId
+    )[0];
+  const walletPda = (wallet: PublicKey) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("wallet"), wallet.toBuffer()],
+      program.programId
+    )[0];
+
+  const fundedKeypair = async () => {
+    const kp = Keypair.generate();
+    const sig = await provider.connection.requestAirdrop(
+      kp.publicKey,
+      2 * LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(sig, "confirmed");
+    return kp;
+  };
+
+  const expectError = async (promise: Promise<unknown>, code: string) => {
+    let err: any;
+    try {
+      await promise;
+    } catch (e) {
+      err = e;
+    }
+    expect(err, `expected ${code}`).to.exist;
+    expect(err.error?.errorCode?.code).to.equal(code);
+  };
+
+  const registerUser = async () => {
+    const user = await fundedKeypair();
+    const phone = nextPhone();
+    await program.methods
+      .registerUser(phone, Array(32).fill(7))
+      .accountsPartial({
+        userAccount: userPda(phone),
+        config: configPda,
+        user: user.publicKey,
+      })
+      .signers([user])
+      .rpc();
+    return { user, phone };
+  };
+
+  const reserveTxId = (wallet: Keypair, txId: number[]) =>
+    program.methods
+      .reserveTxId(txId)
+      .accountsPartial({
+        reservation: reservationPda(txId),
+        user: wallet.publicKey,
+        previousOwner: null,
+      })
+      .signers([wallet]);
+
+  // Reserves the id in the same transaction unless `reserve` is false
+  const createTransaction = async (
+    owner: { user: Keypair; phone: number[] },
+    opts: {
+      txId?: number[];
+      reserve?: boolean;
+      txType?: any;
+      amountNgn?: number;
+      amountUsdc?: number | null;
+      note?: number[] | null;
+      tip?: number | null;
+      parentTxId?: number[] | null;
+      externalRef?: PublicKey | null;
+      priority?: number;
+      guardian?: Keypair;
+      category?: number | null;
+      clientTimestamp?: number | null;
+      riskNonce?: number[];
+      riskSigner?: Keypair;
+    } = {}
+  ) => {
+    const txId = opts.txId ?? newTxId();
+    const preInstructions =
+      opts.reserve === false
+        ? []
+        : [await reserveTxId(owner.user, txId).instruction()];
+    // The risk engine's Ed25519 check must sit right before create_transaction
+    const riskVerify = opts.riskSigner
+      ? Ed25519Program.createInstructionWithPrivateKey({
+          privateKey: opts.riskSigner.secretKey,
+          message: Buffer.concat([
+            Buffer.from(txId),
+            Buffer.from(opts.riskNonce),
+          ]),
+        })
+      : null;
+    if (riskVerify) preInstructions.push(riskVerify);
+    await program.methods
+      .createTransaction(
+        txId,
+        owner.phone,
+        opts.txType ?? { airtime: {} },
+        new BN(opts.amountNgn ?? 1_000),
+        opts.amountUsdc == null ? null : new BN(opts.amountUsdc),
+        opts.note ?? null,
+        opts.tip == null ? null : new BN(opts.tip),
+        opts.parentTxId ?? null,
+        opts.priority ?? 0,
+        opts.category ?? null,
+        opts.clientTimestamp == null ? null : new BN(opts.clientTimestamp),
+        opts.riskNonce ?? null,
+        riskVerify ? Array.from(riskVerify.data.subarray(48, 112)) : null
+      )
+      .accountsPartial({
+        transactionAccount: txPda(txId),
+        parentTransaction: opts.parentTxId ? txPda(opts.parentTxId) : null,
+        externalRef: opts.externalRef ?? null,
+        reservation: reservationPda(txId),
+        userAccount: userPda(owner.phone),
+        userBudget: opts.category == null ? null : budgetPda(owner.phone),
+        config: configPda,
+        user: owner.user.publicKey,
+        guardian: opts.guardian?.publicKey ?? null,
+        instructions: riskVerify ? SYSVAR_INSTRUCTIONS_PUBKEY : null,
+      })
+      .preInstructions(preInstructions)
+      .signers(opts.guardian ? [owner.user, opts.guardian] : [owner.user])
+      .rpc();
+    return txId;
+  };
+
+  // Day number on the validator clock, which keys the DailyStats PDA
+  const chainDay = async () => {
+    const slot = await provider.connection.getSlot();
+    const blockTime = await provider.connection.getBlockTime(slot);
+    return Math.floor(blockTime / 86_400);
+  };
+  const dailyStatsPda = (day: number) =>
+    PublicKey.findProgramAddressSync(
+      [Buffer.from("daily_stats"), new BN(day).toArrayLike(Buffer, "le", 4)],
+      program.programId
+    )[0];
+
+  const forceComplete = async (
+    owner: { user: Keypair; phone: number[] },
+    txId: number[],
+    reason = 0
+  ) =>
+    program.methods
+      .forceComplete(reason)
+      .accountsPartial({
+        transactionAccount: txPda(txId),
+        userAccount: userPda(owner.phone),
+        config: configPda,
+        dailyStats: dailyStatsPda(await chainDay()),
+        admin: admin.publicKey,
+      })
+      .rpc();
+
+  const completeTransaction = async (
+    owner: { user: Keypair; phone: number[] },
+    txId: number[],
+    settler?: Keypair,
+    settlerRole?: PublicKey
+  ) =>
+    program.methods
+      .completeTransaction()
+      .accountsPartial({
+        transactionAccount: txPda(txId),
+        userAccount: userPda(owner.phone),
+        config: configPda,
+        role: settlerRole ?? null,
+        dailyStats: dailyStatsPda(await chainDay()),
+        authority: settler?.publicKey ?? admin.publicKey,
+      })
+      .signers(settler ? [settler] : [])
+      .rpc();
+
+  before(async () => {
+    await program.methods
+      .initializeConfig(50, 100, new BN(100), new BN(1_000_000), 0)
+      .accountsPartial({
+        config: configPda,
+        configSnapshot: configSnapshotPda(0),
+        defaultConfig: null,
+        admin: admin.publicKey,
+      })
+      .rpc();
+  });
+
+  describe("force_complete", () => {
+    it("lets the admin complete a pending transaction", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+
+      await program.methods
+        .forceComplete(3)
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          dailyStats: dailyStatsPda(await chainDay()),
+          admin: admin.publicKey,
+        })
+        .rpc();
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ completed: {} });
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalVolume.toNumber()).to.equal(1_000);
+    });
+
+    it("rejects a signer that is not the admin", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+
+      await expectError(
+        program.methods
+          .forceComplete(1)
+          .accountsPartial({
+            transactionAccount: txPda(txId),
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            dailyStats: dailyStatsPda(await chainDay()),
+            admin: owner.user.publicKey,
+          })
+          .signers([owner.user])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects a transaction that is no longer pending", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      const accounts = {
+        transactionAccount: txPda(txId),
+        userAccount: userPda(owner.phone),
+        config: configPda,
+        dailyStats: dailyStatsPda(await chainDay()),
+        admin: admin.publicKey,
+      };
+
+      await program.methods.forceComplete(1).accountsPartial(accounts).rpc();
+      await expectError(
+        program.methods.forceComplete(1).accountsPartial(accounts).rpc(),
+        "InvalidTransactionStatus"
+      );
+    });
+  });
+
+  describe("promotional transactions", () => {
+    const setPromoConfig = (promoMinLimit: number, maxPromoTxs: number) =>
+      program.methods
+        .setPromoConfig(new BN(promoMinLimit), maxPromoTxs)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    before(() => setPromoConfig(10, 2));
+    after(() => setPromoConfig(100, 0));
+
+    const enablePromo = (phone: number[]) =>
+      program.methods
+        .setUserPromo(true)
+        .accountsPartial({
+          userAccount: userPda(phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+
+    it("rejects amounts below min_limit for regular users", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { amountNgn: 50 }),
+        "TransactionLimitOutOfBounds"
+      );
+    });
+
+    it("lets promo users go down to promo_min_limit with no fee", async () => {
+      const owner = await registerUser();
+      await enablePromo(owner.phone);
+
+      const txId = await createTransaction(owner, { amountNgn: 10 });
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.amountNgn.toNumber()).to.equal(10);
+      expect(tx.fee.toNumber()).to.equal(0);
+
+      await expectError(
+        createTransaction(owner, { amountNgn: 9 }),
+        "TransactionLimitOutOfBounds"
+      );
+    });
+
+    it("never raises the upper bound", async () => {
+      const owner = await registerUser();
+      await enablePromo(owner.phone);
+      await expectError(
+        createTransaction(owner, { amountNgn: 1_000_001 }),
+        "TransactionLimitOutOfBounds"
+      );
+    });
+
+    it("clears the flag after max_promo_transactions", async () => {
+      const owner = await registerUser();
+      await enablePromo(owner.phone);
+
+      await createTransaction(owner, { amountNgn: 20 });
+      await createTransaction(owner, { amountNgn: 20 });
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.promoEnabled).to.equal(false);
+      await expectError(
+        createTransaction(owner, { amountNgn: 20 }),
+        "TransactionLimitOutOfBounds"
+      );
+    });
+
+    it("rejects a non-admin setting the promo flag", async () => {
+      const owner = await registerUser();
+      await expectError(
+        program.methods
+          .setUserPromo(true)
+          .accountsPartial({
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            admin: owner.user.publicKey,
+          })
+          .signers([owner.user])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("usdc amount by transaction type", () => {
+    it("accepts crypto with a positive usdc amount", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, {
+        txType: { crypto: {} },
+        amountUsdc: 650_000,
+      });
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.amountUsdc.toNumber()).to.equal(650_000);
+    });
+
+    it("rejects crypto without a usdc amount", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { txType: { crypto: {} }, amountUsdc: null }),
+        "InvalidUsdcForType"
+      );
+    });
+
+    it("rejects crypto with a zero usdc amount", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { txType: { crypto: {} }, amountUsdc: 0 }),
+        "InvalidUsdcForType"
+      );
+    });
+
+    it("accepts airtime without a usdc amount", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, {
+        txType: { airtime: {} },
+        amountUsdc: null,
+      });
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.amountUsdc).to.equal(null);
+    });
+
+    it("rejects airtime with a usdc amount", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { txType: { airtime: {} }, amountUsdc: 1 }),
+        "InvalidUsdcForType"
+      );
+    });
+  });
+
+  describe("archiving", () => {
+    const archiveAccounts = (
+      owner: { user: Keypair; phone: number[] },
+      txId: number[]
+    ) => ({
+      transactionAccount: txPda(txId),
+      userAccount: userPda(owner.phone),
+      user: owner.user.publicKey,
+    });
+
+    it("archives and restores a settled transaction", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await forceComplete(owner, txId);
+
+      await program.methods
+        .archiveTransaction()
+        .accountsPartial(archiveAccounts(owner, txId))
+        .signers([owner.user])
+        .rpc();
+      let tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.archived).to.equal(true);
+      expect(tx.status).to.deep.equal({ completed: {} });
+
+      await program.methods
+        .unarchiveTransaction()
+        .accountsPartial(archiveAccounts(owner, txId))
+        .signers([owner.user])
+        .rpc();
+      tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.archived).to.equal(false);
+    });
+
+    it("refuses to archive a pending transaction", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await expectError(
+        program.methods
+          .archiveTransaction()
+          .accountsPartial(archiveAccounts(owner, txId))
+          .signers([owner.user])
+          .rpc(),
+        "InvalidTransactionStatus"
+      );
+    });
+
+    it("only lets the owning wallet archive", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      const txId = await createTransaction(owner);
+      await expectError(
+        program.methods
+          .archiveTransaction()
+          .accountsPartial({
+            ...archiveAccounts(owner, txId),
+            user: other.user.publicKey,
+          })
+          .signers([other.user])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("balance enforcement", () => {
+    const setEnforced = (enforced: boolean) =>
+      program.methods
+        .setBalanceEnforced(enforced)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const adjustBalance = (
+      method: "creditBalance" | "debitBalance",
+      phone: number[],
+      amount: number
+    ) =>
+      program.methods[method](new BN(amount))
+        .accountsPartial({
+          userAccount: userPda(phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+
+    before(() => setEnforced(true));
+    after(() => setEnforced(false));
+
+    it("debits amount plus fee from the available balance", async () => {
+      const owner = await registerUser();
+      await adjustBalance("creditBalance", owner.phone, 1_010);
+
+      const txId = await createTransaction(owner, { amountNgn: 1_000 });
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.debitedAmount.toNumber()).to.equal(1_010);
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.availableBalance.toNumber()).to.equal(0);
+    });
+
+    it("rejects a transaction above the available balance", async () => {
+      const owner = await registerUser();
+      await adjustBalance("creditBalance", owner.phone, 500);
+      await expectError(
+        createTransaction(owner, { amountNgn: 1_000 }),
+        "InsufficientBalance"
+      );
+    });
+
+    it("rejects an admin debit above the available balance", async () => {
+      const owner = await registerUser();
+      await adjustBalance("creditBalance", owner.phone, 100);
+      await adjustBalance("debitBalance", owner.phone, 60);
+      await expectError(
+        adjustBalance("debitBalance", owner.phone, 41),
+        "InsufficientBalance"
+      );
+    });
+
+    it("rejects a credit that would overflow the balance", async () => {
+      const owner = await registerUser();
+      await adjustBalance("creditBalance", owner.phone, 1);
+      await expectError(
+        program.methods
+          .creditBalance(new BN("18446744073709551615"))
+          .accountsPartial({
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            admin: admin.publicKey,
+          })
+          .rpc(),
+        "ArithmeticOverflow"
+      );
+    });
+  });
+
+  describe("per-type settlers", () => {
+    const cryptoSettler = Keypair.generate();
+    const airtimeSettler = Keypair.generate();
+    const setSettlers = (crypto: PublicKey, airtime: PublicKey) =>
+      program.methods
+        .setSettlers(crypto, airtime)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    before(() =>
+      setSettlers(cryptoSettler.publicKey, airtimeSettler.publicKey)
+    );
+    after(() => setSettlers(admin.publicKey, admin.publicKey));
+
+    it("lets each settler complete its own transaction type", async () => {
+      const owner = await registerUser();
+      const cryptoTx = await createTransaction(owner, {
+        txType: { crypto: {} },
+        amountUsdc: 650_000,
+      });
+      const airtimeTx = await createTransaction(owner);
+
+      await completeTransaction(owner, cryptoTx, cryptoSettler);
+      await completeTransaction(owner, airtimeTx, airtimeSettler);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalVolume.toNumber()).to.equal(2_000);
+    });
+
+    it("rejects cross-type settlement", async () => {
+      const owner = await registerUser();
+      const cryptoTx = await createTransaction(owner, {
+        txType: { crypto: {} },
+        amountUsdc: 650_000,
+      });
+      const airtimeTx = await createTransaction(owner);
+
+      await expectError(
+        completeTransaction(owner, cryptoTx, airtimeSettler),
+        "Unauthorized"
+      );
+      await expectError(
+        completeTransaction(owner, airtimeTx, cryptoSettler),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects the admin once settlers are delegated", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await expectError(completeTransaction(owner, txId), "Unauthorized");
+    });
+  });
+
+  describe("transaction notes", () => {
+    it("stores the encrypted note verbatim", async () => {
+      const owner = await registerUser();
+      const note = Array.from({ length: 64 }, (_, i) => (i * 7) % 256);
+      const txId = await createTransaction(owner, { note });
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.noteCiphertext).to.deep.equal(note);
+    });
+
+    it("leaves the note empty when none is supplied", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.noteCiphertext).to.equal(null);
+    });
+  });
+
+  describe("airtime denominations", () => {
+    const setDenominations = (values: number[]) =>
+      program.methods
+        .setAirtimeDenominations(
+          [...values, ...Array(8 - values.length).fill(0)].map((v) => new BN(v))
+        )
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    before(() => setDenominations([100, 200, 500, 1_000]));
+    after(() => setDenominations([]));
+
+    it("accepts an allowed denomination", async () => {
+      const owner = await registerUser();
+      await createTransaction(owner, { amountNgn: 500 });
+    });
+
+    it("rejects airtime amounts outside the list", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { amountNgn: 300 }),
+        "InvalidDenomination"
+      );
+    });
+
+    it("does not restrict crypto amounts", async () => {
+      const owner = await registerUser();
+      await createTransaction(owner, {
+        txType: { crypto: {} },
+        amountNgn: 300,
+        amountUsdc: 195_000,
+      });
+    });
+
+    it("allows any amount once the list is cleared", async () => {
+      await setDenominations([]);
+      const owner = await registerUser();
+      await createTransaction(owner, { amountNgn: 300 });
+      await setDenominations([100, 200, 500, 1_000]);
+    });
+  });
+
+  describe("maintenance freeze", () => {
+    const setFrozen = (frozen: boolean) =>
+      program.methods
+        .setNewTxFrozen(frozen)
+        .accountsPartial({
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    after(() => setFrozen(false));
+
+    it("blocks new transactions but still settles pending ones", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+
+      await setFrozen(true);
+      await expectError(createTransaction(owner), "NewTransactionsFrozen");
+
+      await completeTransaction(owner, txId);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ completed: {} });
+
+      await setFrozen(false);
+      await createTransaction(owner);
+    });
+  });
+
+  describe("fee computation", () => {
+    it("stores the applied bps alongside the computed fee", async () => {
+      const owner = await registerUser();
+      const config = await program.account.config.fetch(configPda);
+
+      const airtimeTx = await createTransaction(owner, { amountNgn: 1_999 });
+      const cryptoTx = await createTransaction(owner, {
+        txType: { crypto: {} },
+        amountNgn: 1_999,
+        amountUsdc: 1_300_000,
+      });
+
+      const airtime = await program.account.transactionAccount.fetch(
+        txPda(airtimeTx)
+      );
+      const crypto = await program.account.transactionAccount.fetch(
+        txPda(cryptoTx)
+      );
+      expect(airtime.appliedFeeBps).to.equal(config.airtimeFeeBps);
+      expect(crypto.appliedFeeBps).to.equal(config.cryptoFeeBps);
+
+      for (const tx of [airtime, crypto]) {
+        const recomputed = Math.floor(
+          (tx.amountNgn.toNumber() * tx.appliedFeeBps) / 10_000
+        );
+        expect(tx.fee.toNumber()).to.equal(recomputed);
+      }
+    });
+  });
+
+  describe("registration cap", () => {
+    const setMaxUsers = (maxUsers: number) =>
+      program.methods
+        .setMaxUsers(new BN(maxUsers))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    after(() => setMaxUsers(0));
+
+    it("counts registrations", async () => {
+      const previous = await program.account.config.fetch(configPda);
+      await registerUser();
+      const current = await program.account.config.fetch(configPda);
+      expect(current.totalUsers.toNumber()).to.equal(
+        previous.totalUsers.toNumber() + 1
+      );
+    });
+
+    it("rejects registrations once the cap is reached", async () => {
+      const config = await program.account.config.fetch(configPda);
+      await setMaxUsers(config.totalUsers.toNumber() + 1);
+
+      await registerUser();
+      await expectError(registerUser(), "RegistrationCapReached");
+
+      await setMaxUsers(0);
+      await registerUser();
+    });
+  });
+
+  describe("tips", () => {
+    const tipRecipient = Keypair.generate().publicKey;
+    const setTipConfig = (recipient: PublicKey, maxTip: number) =>
+      program.methods
+        .setTipConfig(recipient, new BN(maxTip))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    before(() => setTipConfig(tipRecipient, 500));
+    after(() => setTipConfig(admin.publicKey, 0));
+
+    it("records the tip separately and accumulates it", async () => {
+      const owner = await registerUser();
+      const previous = await program.account.config.fetch(configPda);
+
+      const txId = await createTransaction(owner, {
+        amountNgn: 1_000,
+        tip: 200,
+      });
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.tipNgn.toNumber()).to.equal(200);
+      expect(tx.amountNgn.toNumber()).to.equal(1_000);
+      expect(tx.fee.toNumber()).to.equal(10);
+
+      const config = await program.account.config.fetch(configPda);
+      expect(config.tipRecipient.toBase58()).to.equal(tipRecipient.toBase58());
+      expect(config.totalTips.toNumber()).to.equal(
+        previous.totalTips.toNumber() + 200
+      );
+    });
+
+    it("rejects tips above the cap", async () => {
+      const owner = await registerUser();
+      await expectError(createTransaction(owner, { tip: 501 }), "TipTooLarge");
+    });
+  });
+
+  describe("tx_id reservations", () => {
+    it("consumes and closes the reservation on create", async () => {
+      const owner = await registerUser();
+      const txId = newTxId();
+      await reserveTxId(owner.user, txId).rpc();
+
+      const reservation = await program.account.txReservation.fetch(
+        reservationPda(txId)
+      );
+      expect(reservation.owner.toBase58()).to.equal(
+        owner.user.publicKey.toBase58()
+      );
+
+      await createTransaction(owner, { txId, reserve: false });
+      const closed = await provider.connection.getAccountInfo(
+        reservationPda(txId)
+      );
+      expect(closed).to.equal(null);
+    });
+
+    it("requires a reservation", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { reserve: false }),
+        "AccountNotInitialized"
+      );
+    });
+
+    it("only lets the user's own wallet create for their phone", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      await expectError(
+        createTransaction({ user: other.user, phone: owner.phone }),
+        "Unauthorized"
+      );
+    });
+
+    it("stops another wallet from taking a reserved id", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      const txId = newTxId();
+      await reserveTxId(owner.user, txId).rpc();
+
+      await expectError(reserveTxId(other.user, txId).rpc(), "TxIdReserved");
+      await expectError(
+        createTransaction(other, { txId, reserve: false }),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("velocity check", () => {
+    const setVelocity = (multiplier: number, minHistory: number) =>
+      program.methods
+        .setVelocityConfig(multiplier, minHistory)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    before(() => setVelocity(3, 3));
+    after(() => setVelocity(0, 0));
+
+    it("exempts accounts without enough history", async () => {
+      const owner = await registerUser();
+      await createTransaction(owner, { amountNgn: 1_000 });
+      await createTransaction(owner, { amountNgn: 50_000 });
+    });
+
+    it("rejects amounts above avg_amount * multiplier", async () => {
+      const owner = await registerUser();
+      for (let i = 0; i < 3; i++) {
+        await createTransaction(owner, { amountNgn: 1_000 });
+      }
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.avgAmount.toNumber()).to.equal(1_000);
+
+      await expectError(
+        createTransaction(owner, { amountNgn: 3_001 }),
+        "VelocityExceeded"
+      );
+      await createTransaction(owner, { amountNgn: 3_000 });
+    });
+  });
+
+  describe("delegated roles", () => {
+    const CAN_PAUSE = 1 << 0;
+    const CAN_UPDATE_FEES = 1 << 1;
+    const CAN_SUSPEND_USERS = 1 << 2;
+    const CAN_SETTLE = 1 << 3;
+
+    const grantRole = (grantee: PublicKey, permissions: number) =>
+      program.methods
+        .grantRole(grantee, permissions)
+        .accountsPartial({
+          role: rolePda(grantee),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+    const revokeRole = (grantee: PublicKey) =>
+      program.methods
+        .revokeRole()
+        .accountsPartial({
+          role: rolePda(grantee),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+    const setPaused = (operator: Keypair, paused: boolean) =>
+      program.methods
+        .setPaused(paused)
+        .accountsPartial({
+          config: configPda,
+          role: rolePda(operator.publicKey),
+          authority: operator.publicKey,
+        })
+        .signers([operator])
+        .rpc();
+    const setUserStatus = (
+      method: "suspendUser" | "reactivateUser",
+      operator: Keypair,
+      phone: number[]
+    ) =>
+      program.methods[method]()
+        .accountsPartial({
+          userAccount: userPda(phone),
+          config: configPda,
+          role: rolePda(operator.publicKey),
+          authority: operator.publicKey,
+        })
+        .signers([operator])
+        .rpc();
+
+    it("lets a pause role pause, and force_complete still works", async () => {
+      const operator = Keypair.generate();
+      await grantRole(operator.publicKey, CAN_PAUSE);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+
+      await setPaused(operator, true);
+      try {
+        await expectError(createTransaction(owner), "ContractPaused");
+        await forceComplete(owner, txId);
+      } finally {
+        await setPaused(operator, false);
+      }
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ completed: {} });
+    });
+
+    it("only allows the granted permission bits", async () => {
+      const operator = await fundedKeypair();
+      await grantRole(operator.publicKey, CAN_PAUSE);
+      await expectError(
+        program.methods
+          .updateConfig(50, 100, new BN(100), new BN(1_000_000))
+          .accountsPartial({
+            config: configPda,
+            configSnapshot: await nextConfigSnapshot(),
+            role: rolePda(operator.publicKey),
+            authority: operator.publicKey,
+          })
+          .signers([operator])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("lets a fee role update fees but validates the values", async () => {
+      const operator = await fundedKeypair();
+      await grantRole(operator.publicKey, CAN_UPDATE_FEES);
+      const update = async (minLimit: number, maxLimit: number) =>
+        program.methods
+          .updateConfig(50, 100, new BN(minLimit), new BN(maxLimit))
+          .accountsPartial({
+            config: configPda,
+            configSnapshot: await nextConfigSnapshot(),
+            role: rolePda(operator.publicKey),
+            authority: operator.publicKey,
+          })
+          .signers([operator])
+          .rpc();
+
+      await expectError(update(2_000, 1_000), "InvalidConfig");
+      await update(100, 1_000_000);
+    });
+
+    it("lets a fee role set the fee bounds", async () => {
+      const operator = await fundedKeypair();
+      await grantRole(operator.publicKey, CAN_UPDATE_FEES);
+      const setFeeBounds = (min: number, max: number) =>
+        program.methods
+          .setFeeBounds(new BN(min), new BN(max))
+          .accountsPartial({
+            config: configPda,
+            role: rolePda(operator.publicKey),
+            authority: operator.publicKey,
+          })
+          .signers([operator])
+          .rpc();
+
+      await setFeeBounds(50, 60);
+      try {
+        const config = await program.account.config.fetch(configPda);
+        expect(config.minFeeNgn.toNumber()).to.equal(50);
+        expect(config.maxFeeNgn.toNumber()).to.equal(60);
+      } finally {
+        await setFeeBounds(0, 0);
+      }
+    });
+
+    it("stops working once revoked", async () => {
+      const operator = Keypair.generate();
+      await grantRole(operator.publicKey, CAN_PAUSE);
+      await revokeRole(operator.publicKey);
+      await expectError(
+        program.methods
+          .setPaused(true)
+          .accountsPartial({
+            config: configPda,
+            role: null,
+            authority: operator.publicKey,
+          })
+          .signers([operator])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("lets a suspend role suspend and reactivate users", async () => {
+      const operator = Keypair.generate();
+      await grantRole(operator.publicKey, CAN_SUSPEND_USERS);
+      const owner = await registerUser();
+
+      await setUserStatus("suspendUser", operator, owner.phone);
+      await expectError(createTransaction(owner), "UserSuspended");
+
+      await setUserStatus("reactivateUser", operator, owner.phone);
+      await createTransaction(owner);
+    });
+
+    it("lets a settle role complete any transaction type", async () => {
+      const operator = Keypair.generate();
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+
+      await expectError(
+        completeTransaction(owner, txId, operator),
+        "Unauthorized"
+      );
+
+      await grantRole(operator.publicKey, CAN_SETTLE);
+      await completeTransaction(
+        owner,
+        txId,
+        operator,
+        rolePda(operator.publicKey)
+      );
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ completed: {} });
+    });
+  });
+
+  describe("simulate_transaction", () => {
+    // Anchor error codes: 6000 + position in OuhError
+    const LIMIT_OUT_OF_BOUNDS = 6001;
+    const USER_SUSPENDED = 6003;
+    const TIP_TOO_LARGE = 6012;
+
+    const simulate = (
+      phone: number[],
+      amountNgn: number,
+      opts: { tip?: number; priority?: number } = {}
+    ) =>
+      program.methods
+        .simulateTransaction(
+          { airtime: {} },
+          new BN(amountNgn),
+          null,
+          opts.tip == null ? null : new BN(opts.tip),
+          opts.priority ?? 0,
+          null
+        )
+        .accountsPartial({ userAccount: userPda(phone), config: configPda })
+        .view();
+
+    it("reports the fee for a transaction that would succeed", async () => {
+      const owner = await registerUser();
+      const result = await simulate(owner.phone, 2_000);
+      expect(result.wouldSucceed).to.equal(true);
+      expect(result.reasonCode).to.equal(0);
+      expect(result.fee.toNumber()).to.equal(20);
+    });
+
+    it("reports why a transaction would fail", async () => {
+      const owner = await registerUser();
+      const result = await simulate(owner.phone, 1);
+      expect(result.wouldSucceed).to.equal(false);
+      expect(result.reasonCode).to.equal(LIMIT_OUT_OF_BOUNDS);
+
+      await program.methods
+        .suspendUser()
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+      const suspended = await simulate(owner.phone, 2_000);
+      expect(suspended.reasonCode).to.equal(USER_SUSPENDED);
+    });
+
+    it("checks the tip like create_transaction does", async () => {
+      const owner = await registerUser();
+      const result = await simulate(owner.phone, 2_000, { tip: 1 });
+      expect(result.wouldSucceed).to.equal(false);
+      expect(result.reasonCode).to.equal(TIP_TOO_LARGE);
+    });
+  });
+
+  describe("transaction chaining", () => {
+    it("links a child to a settled parent and walks the chain", async () => {
+      const owner = await registerUser();
+      const rootId = await createTransaction(owner);
+      await completeTransaction(owner, rootId);
+      const childId = await createTransaction(owner, { parentTxId: rootId });
+      await completeTransaction(owner, childId);
+      const grandchildId = await createTransaction(owner, {
+        parentTxId: childId,
+      });
+
+      const grandchild = await program.account.transactionAccount.fetch(
+        txPda(grandchildId)
+      );
+      expect(grandchild.parentTxId).to.deep.equal(childId);
+
+      const chain = await program.methods
+        .getTransactionChain()
+        .accountsPartial({ transactionAccount: txPda(grandchildId) })
+        .remainingAccounts(
+          [childId, rootId].map((id) => ({
+            pubkey: txPda(id),
+            isSigner: false,
+            isWritable: false,
+          }))
+        )
+        .view();
+      expect(chain).to.deep.equal([grandchildId, childId, rootId]);
+    });
+
+    it("rejects a parent that is still pending", async () => {
+      const owner = await registerUser();
+      const parentId = await createTransaction(owner);
+      await expectError(
+        createTransaction(owner, { parentTxId: parentId }),
+        "InvalidParentTransaction"
+      );
+    });
+
+    it("rejects a parent belonging to another user", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      const parentId = await createTransaction(other);
+      await completeTransaction(other, parentId);
+      await expectError(
+        createTransaction(owner, { parentTxId: parentId }),
+        "InvalidParentTransaction"
+      );
+    });
+  });
+
+  describe("cancel_transaction", () => {
+    const cancel = (
+      owner: { user: Keypair; phone: number[] },
+      txId: number[],
+      signer: Keypair = owner.user
+    ) =>
+      program.methods
+        .cancelTransaction()
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          user: signer.publicKey,
+        })
+        .signers([signer])
+        .rpc();
+    const setEnforced = (enforced: boolean) =>
+      program.methods
+        .setBalanceEnforced(enforced)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    it("cancels a pending transaction and releases the hold", async () => {
+      const owner = await registerUser();
+      await program.methods
+        .creditBalance(new BN(5_000))
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+
+      await setEnforced(true);
+      let txId: number[];
+      try {
+        txId = await createTransaction(owner, { amountNgn: 1_000 });
+      } finally {
+        await setEnforced(false);
+      }
+      let user = await program.account.userAccount.fetch(userPda(owner.phone));
+      expect(user.availableBalance.toNumber()).to.equal(3_990);
+
+      await cancel(owner, txId);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ cancelled: {} });
+      user = await program.account.userAccount.fetch(userPda(owner.phone));
+      expect(user.availableBalance.toNumber()).to.equal(5_000);
+    });
+
+    it("rejects cancelling a completed transaction", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await completeTransaction(owner, txId);
+      await expectError(cancel(owner, txId), "InvalidTransactionStatus");
+    });
+
+    it("only lets the owning wallet cancel", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      const txId = await createTransaction(owner);
+      await expectError(cancel(owner, txId, other.user), "Unauthorized");
+    });
+  });
+
+  describe("free first transactions", () => {
+    const setFreeTxCount = (count: number) =>
+      program.methods
+        .setFreeTxCount(count)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    after(() => setFreeTxCount(0));
+
+    it("waives fees until the free allowance is used up", async () => {
+      await setFreeTxCount(1);
+      const owner = await registerUser();
+      let user = await program.account.userAccount.fetch(userPda(owner.phone));
+      expect(user.freeTxRemaining).to.equal(1);
+
+      const freeTx = await createTransaction(owner, { amountNgn: 2_000 });
+      let tx = await program.account.transactionAccount.fetch(txPda(freeTx));
+      expect(tx.fee.toNumber()).to.equal(0);
+      expect(tx.appliedFeeBps).to.equal(0);
+
+      await completeTransaction(owner, freeTx);
+      user = await program.account.userAccount.fetch(userPda(owner.phone));
+      expect(user.freeTxRemaining).to.equal(0);
+
+      const paidTx = await createTransaction(owner, { amountNgn: 2_000 });
+      tx = await program.account.transactionAccount.fetch(txPda(paidTx));
+      const config = await program.account.config.fetch(configPda);
+      expect(tx.appliedFeeBps).to.equal(config.airtimeFeeBps);
+      expect(tx.fee.toNumber()).to.equal(
+        Math.floor((2_000 * config.airtimeFeeBps) / 10_000)
+      );
+    });
+  });
+
+  describe("close_transactions_batch", () => {
+    const closeBatch = (
+      owner: { user: Keypair; phone: number[] },
+      txIds: number[][]
+    ) =>
+      program.methods
+        .closeTransactionsBatch()
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          user: owner.user.publicKey,
+        })
+        .remainingAccounts(
+          txIds.map((txId) => ({
+            pubkey: txPda(txId),
+            isSigner: false,
+            isWritable: true,
+          }))
+        )
+        .signers([owner.user])
+        .rpc();
+
+    it("closes settled transactions and skips pending ones", async () => {
+      const owner = await registerUser();
+      const settled = await createTransaction(owner);
+      await completeTransaction(owner, settled);
+      const pending = await createTransaction(owner);
+
+      const previous = await provider.connection.getBalance(
+        owner.user.publicKey
+      );
+      await closeBatch(owner, [settled, pending]);
+      const current = await provider.connection.getBalance(
+        owner.user.publicKey
+      );
+      expect(current).to.be.greaterThan(previous);
+
+      expect(
+        await program.account.transactionAccount.fetchNullable(txPda(settled))
+      ).to.be.null;
+      const remaining = await program.account.transactionAccount.fetch(
+        txPda(pending)
+      );
+      expect(remaining.status).to.deep.equal({ pending: {} });
+    });
+
+    it("rejects transactions belonging to another user", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      const txId = await createTransaction(other);
+      await completeTransaction(other, txId);
+
+      await expectError(closeBatch(owner, [txId]), "Unauthorized");
+    });
+
+    it("rejects oversized batches", async () => {
+      const owner = await registerUser();
+      const txIds = Array.from({ length: 17 }, () => newTxId());
+      await expectError(closeBatch(owner, txIds), "BatchTooLarge");
+    });
+  });
+
+  describe("usdc precision", () => {
+    const setUsdcMinUnit = (unit: number) =>
+      program.methods
+        .setUsdcMinUnit(new BN(unit))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    after(() => setUsdcMinUnit(0));
+
+    it("rejects usdc amounts not aligned to the minimum unit", async () => {
+      const owner = await registerUser();
+      await setUsdcMinUnit(10_000);
+
+      await expectError(
+        createTransaction(owner, {
+          txType: { crypto: {} },
+          amountUsdc: 1_300_001,
+        }),
+        "InvalidPrecision"
+      );
+      await createTransaction(owner, {
+        txType: { crypto: {} },
+        amountUsdc: 1_300_000,
+      });
+    });
+
+    it("rejects usdc amounts above the upper bound", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, {
+          txType: { crypto: {} },
+          amountUsdc: 1_000_000_000_001,
+        }),
+        "InvalidPrecision"
+      );
+    });
+  });
+
+  describe("get_user_tx_page", () => {
+    const getPage = (
+      owner: { user: Keypair; phone: number[] },
+      fromSeq: number,
+      limit: number,
+      txIds: number[][],
+      includeArchived = false
+    ) =>
+      program.methods
+        .getUserTxPage(new BN(fromSeq), limit, includeArchived)
+        .accountsPartial({ userAccount: userPda(owner.phone) })
+        .remainingAccounts(
+          txIds.map((txId) => ({
+            pubkey: txPda(txId),
+            isSigner: false,
+            isWritable: false,
+          }))
+        );
+
+    it("assigns sequence numbers per user", async () => {
+      const owner = await registerUser();
+      const first = await createTransaction(owner);
+      const second = await createTransaction(owner);
+
+      const a = await program.account.transactionAccount.fetch(txPda(first));
+      const b = await program.account.transactionAccount.fetch(txPda(second));
+      expect(a.userSeq.toNumber()).to.equal(0);
+      expect(b.userSeq.toNumber()).to.equal(1);
+    });
+
+    it("returns summaries within the requested range", async () => {
+      const owner = await registerUser();
+      const txIds: number[][] = [];
+      for (let i = 0; i < 3; i++) {
+        txIds.push(await createTransaction(owner));
+      }
+
+      const page = await getPage(owner, 1, 2, txIds).view();
+      expect(page.map((s: any) => s.userSeq.toNumber())).to.deep.equal([1, 2]);
+      expect(page[0].txId).to.deep.equal(txIds[1]);
+    });
+
+    it("leaves archived transactions out unless asked", async () => {
+      const owner = await registerUser();
+      const txIds = [
+        await createTransaction(owner),
+        await createTransaction(owner),
+      ];
+      await forceComplete(owner, txIds[0]);
+      await program.methods
+        .archiveTransaction()
+        .accountsPartial({
+          transactionAccount: txPda(txIds[0]),
+          userAccount: userPda(owner.phone),
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+      const active = await getPage(owner, 0, 10, txIds).view();
+      expect(active.map((s: any) => s.txId)).to.deep.equal([txIds[1]]);
+      const all = await getPage(owner, 0, 10, txIds, true).view();
+      expect(all.map((s: any) => s.archived)).to.deep.equal([true, false]);
+    });
+
+    it("rejects transactions of another user", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      const txId = await createTransaction(other);
+
+      await expectError(
+        getPage(owner, 0, 10, [txId]).rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("business hours", () => {
+    const AIRTIME_FLAG = 1 << 1;
+    const ALL_WEEKDAYS = 0b111_1111;
+
+    const setBusinessHours = (
+      restrictedTxTypes: number,
+      businessDays: number,
+      businessHours: number[] | null = null
+    ) =>
+      program.methods
+        .setBusinessHours(restrictedTxTypes, businessDays, businessHours)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    // Weekday (0 is Sunday) and UTC hour of the cluster clock
+    const clusterNow = async () => {
+      const slot = await provider.connection.getSlot();
+      const blockTime = await provider.connection.getBlockTime(slot);
+      const date = new Date(blockTime * 1000);
+      return { weekday: date.getUTCDay(), hour: date.getUTCHours() };
+    };
+
+    after(() => setBusinessHours(0, ALL_WEEKDAYS));
+
+    it("rejects flagged types on a closed weekday", async () => {
+      const owner = await registerUser();
+      const { weekday } = await clusterNow();
+      await setBusinessHours(AIRTIME_FLAG, ALL_WEEKDAYS & ~(1 << weekday));
+
+      await expectError(createTransaction(owner), "OutsideBusinessHours");
+      // Unflagged types are always allowed
+      await createTransaction(owner, {
+        txType: { crypto: {} },
+        amountUsdc: 1_300_000,
+      });
+    });
+
+    it("treats the neighbouring days as closed", async () => {
+      const owner = await registerUser();
+      const { weekday } = await clusterNow();
+      const yesterday = (weekday + 6) % 7;
+      const tomorrow = (weekday + 1) % 7;
+      await setBusinessHours(AIRTIME_FLAG, (1 << yesterday) | (1 << tomorrow));
+      await expectError(createTransaction(owner), "OutsideBusinessHours");
+
+      await setBusinessHours(AIRTIME_FLAG, 1 << weekday);
+      await createTransaction(owner);
+    });
+
+    it("applies hour windows that wrap past midnight", async () => {
+      const owner = await registerUser();
+      const { hour } = await clusterNow();
+      const nextHour = (hour + 1) % 24;
+
+      // Open every hour except the current one
+      await setBusinessHours(AIRTIME_FLAG, ALL_WEEKDAYS, [nextHour, hour]);
+      await expectError(createTransaction(owner), "OutsideBusinessHours");
+
+      // Open only for the current hour
+      await setBusinessHours(AIRTIME_FLAG, ALL_WEEKDAYS, [hour, nextHour]);
+      await createTransaction(owner);
+    });
+  });
+
+  describe("get_uptime", () => {
+    it("reports the time since initialization", async () => {
+      const config = await program.account.config.fetch(configPda);
+      expect(config.initializedAt.toNumber()).to.be.greaterThan(0);
+
+      const uptime = await program.methods
+        .getUptime()
+        .accountsPartial({ config: configPda })
+        .view();
+      expect(uptime.toNumber()).to.be.at.least(0);
+    });
+  });
+
+  describe("retry_transaction", () => {
+    const setMaxRetries = (maxRetries: number) =>
+      program.methods
+        .setMaxRetries(maxRetries)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const updateStatus = (
+      method: "failTransaction" | "retryTransaction",
+      owner: { user: Keypair; phone: number[] },
+      txId: number[]
+    ) =>
+      program.methods[method]()
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    before(() => setMaxRetries(1));
+    after(() => setMaxRetries(0));
+
+    it("moves a failed transaction back to pending", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await updateStatus("failTransaction", owner, txId);
+      await updateStatus("retryTransaction", owner, txId);
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ pending: {} });
+      expect(tx.retryCount).to.equal(1);
+
+      await completeTransaction(owner, txId);
+    });
+
+    it("leaves the transaction failed once retries run out", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await updateStatus("failTransaction", owner, txId);
+      await updateStatus("retryTransaction", owner, txId);
+      await updateStatus("failTransaction", owner, txId);
+
+      await expectError(
+        updateStatus("retryTransaction", owner, txId),
+        "RetryLimitReached"
+      );
+      await expectError(
+        completeTransaction(owner, txId),
+        "InvalidTransactionStatus"
+      );
+    });
+
+    it("only retries failed transactions", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await expectError(
+        updateStatus("retryTransaction", owner, txId),
+        "InvalidTransactionStatus"
+      );
+    });
+
+    it("rejects callers that are not settlers", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      const stranger = await fundedKeypair();
+      await expectError(
+        program.methods
+          .failTransaction()
+          .accountsPartial({
+            transactionAccount: txPda(txId),
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            role: null,
+            authority: stranger.publicKey,
+          })
+          .signers([stranger])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("registration deposit", () => {
+    const DEPOSIT = 1_000_000;
+    const setDeposit = (deposit: number) =>
+      program.methods
+        .setRegistrationDeposit(new BN(deposit))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const closeUser = (owner: { user: Keypair; phone: number[] }) =>
+      program.methods
+        .closeUser()
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+    after(() => setDeposit(0));
+
+    it("locks the deposit in the user PDA on top of rent", async () => {
+      await setDeposit(DEPOSIT);
+      const owner = await registerUser();
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.depositLamports.toNumber()).to.equal(DEPOSIT);
+      const info = await provider.connection.getAccountInfo(
+        userPda(owner.phone)
+      );
+      const rent =
+        await provider.connection.getMinimumBalanceForRentExemption(
+          info.data.length
+        );
+      expect(info.lamports).to.equal(rent + DEPOSIT);
+    });
+
+    it("refunds the deposit when the user closes their account", async () => {
+      await setDeposit(DEPOSIT);
+      const owner = await registerUser();
+      const previous = await provider.connection.getBalance(
+        owner.user.publicKey
+      );
+
+      await closeUser(owner);
+      const current = await provider.connection.getBalance(
+        owner.user.publicKey
+      );
+      expect(current - previous).to.be.greaterThan(DEPOSIT);
+      expect(
+        await program.account.userAccount.fetchNullable(userPda(owner.phone))
+      ).to.be.null;
+    });
+
+    it("keeps accounts with an available balance open", async () => {
+      const owner = await registerUser();
+      await program.methods
+        .creditBalance(new BN(100))
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+      await expectError(closeUser(owner), "BalanceNotEmpty");
+    });
+  });
+
+  describe("attest_volume", () => {
+    it("binds the volume and timestamp to the program", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 2_500 });
+      await completeTransaction(owner, txId);
+
+      const attestation = await program.methods
+        .attestVolume()
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+        })
+        .view();
+      expect(attestation.phone).to.deep.equal(owner.phone);
+      expect(attestation.totalVolume.toNumber()).to.equal(2_500);
+
+      const digest = createHash("sha256")
+        .update(program.programId.toBuffer())
+        .update(configPda.toBuffer())
+        .update(Buffer.from(owner.phone))
+        .update(attestation.totalVolume.toArrayLike(Buffer, "le", 8))
+        .update(attestation.timestamp.toTwos(64).toArrayLike(Buffer, "le", 8))
+        .digest();
+      expect(Buffer.from(attestation.digest)).to.deep.equal(digest);
+    });
+  });
+
+  describe("zero amounts", () => {
+    const setMinLimit = async (minLimit: number) =>
+      program.methods
+        .updateConfig(50, 100, new BN(minLimit), new BN(1_000_000))
+        .accountsPartial({
+          config: configPda,
+          configSnapshot: await nextConfigSnapshot(),
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    after(() => setMinLimit(100));
+
+    it("rejects a zero amount even when min_limit is zero", async () => {
+      await setMinLimit(0);
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { amountNgn: 0 }),
+        "ZeroAmount"
+      );
+      await createTransaction(owner, { amountNgn: 1 });
+    });
+  });
+
+  describe("external references", () => {
+    const setPartnerProgram = (partnerProgram: PublicKey) =>
+      program.methods
+        .setPartnerProgram(partnerProgram)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    // System-owned wallets stand in for partner-owned order accounts
+    before(() => setPartnerProgram(anchor.web3.SystemProgram.programId));
+    after(() => setPartnerProgram(PublicKey.default));
+
+    it("stores an order owned by the partner program", async () => {
+      const owner = await registerUser();
+      const order = await fundedKeypair();
+      const txId = await createTransaction(owner, {
+        externalRef: order.publicKey,
+      });
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.externalRef.toBase58()).to.equal(order.publicKey.toBase58());
+    });
+
+    it("rejects an order owned by another program", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { externalRef: configPda }),
+        "InvalidExternalRef"
+      );
+    });
+  });
+
+  describe("usdc exposure cap", () => {
+    const setMaxExposure = (maxExposure: number) =>
+      program.methods
+        .setMaxUserUsdcExposure(new BN(maxExposure))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const createCrypto = (
+      owner: { user: Keypair; phone: number[] },
+      amountUsdc: number
+    ) => createTransaction(owner, { txType: { crypto: {} }, amountUsdc });
+
+    after(() => setMaxExposure(0));
+
+    it("accumulates usdc volume on completed crypto transactions", async () => {
+      const owner = await registerUser();
+      const txId = await createCrypto(owner, 1_300_000);
+      await completeTransaction(owner, txId);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalUsdcVolume.toNumber()).to.equal(1_300_000);
+    });
+
+    it("allows exposure up to the cap and rejects beyond it", async () => {
+      await setMaxExposure(2_000_000);
+      const owner = await registerUser();
+      const txId = await createCrypto(owner, 1_300_000);
+      await completeTransaction(owner, txId);
+
+      await expectError(createCrypto(owner, 700_001), "UsdcExposureExceeded");
+      await createCrypto(owner, 700_000);
+    });
+
+    it("counts pending crypto transactions against the cap", async () => {
+      await setMaxExposure(2_000_000);
+      const owner = await registerUser();
+      const txId = await createCrypto(owner, 1_300_000);
+
+      await expectError(createCrypto(owner, 700_001), "UsdcExposureExceeded");
+      const pending = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(pending.pendingUsdc.toNumber()).to.equal(1_300_000);
+
+      // Settling moves it across without counting it twice
+      await completeTransaction(owner, txId);
+      const settled = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(settled.pendingUsdc.toNumber()).to.equal(0);
+      expect(settled.totalUsdcVolume.toNumber()).to.equal(1_300_000);
+      await createCrypto(owner, 700_000);
+    });
+  });
+
+  describe("ban_user", () => {
+    const ban = (phone: number[]) =>
+      program.methods
+        .banUser()
+        .accountsPartial({
+          userAccount: userPda(phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+    const setUserStatus = (
+      method: "suspendUser" | "reactivateUser",
+      phone: number[]
+    ) =>
+      program.methods[method]()
+        .accountsPartial({
+          userAccount: userPda(phone),
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    it("rejects transactions from a banned user", async () => {
+      const owner = await registerUser();
+      await ban(owner.phone);
+      await expectError(createTransaction(owner), "UserBanned");
+    });
+
+    it("cannot be undone by suspend or reactivate", async () => {
+      const owner = await registerUser();
+      await ban(owner.phone);
+
+      await expectError(
+        setUserStatus("reactivateUser", owner.phone),
+        "UserBanned"
+      );
+      await expectError(
+        setUserStatus("suspendUser", owner.phone),
+        "UserBanned"
+      );
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.status).to.deep.equal({ banned: {} });
+    });
+
+    it("is admin only", async () => {
+      const owner = await registerUser();
+      const stranger = await fundedKeypair();
+      await expectError(
+        program.methods
+          .banUser()
+          .accountsPartial({
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            admin: stranger.publicKey,
+          })
+          .signers([stranger])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("micro airtime fees", () => {
+    const setFeeFreeBelow = (threshold: number) =>
+      program.methods
+        .setAirtimeFeeFreeBelow(new BN(threshold))
+        .accountsPartial({
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+    const feeFor = async (
+      owner: { user: Keypair; phone: number[] },
+      amountNgn: number
+    ) => {
+      const txId = await createTransaction(owner, { amountNgn });
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      return tx.fee.toNumber();
+    };
+
+    before(() => setFeeFreeBelow(500));
+    after(() => setFeeFreeBelow(0));
+
+    it("waives the fee just below the threshold", async () => {
+      const owner = await registerUser();
+      expect(await feeFor(owner, 499)).to.equal(0);
+    });
+
+    it("charges the normal fee at and above the threshold", async () => {
+      const owner = await registerUser();
+      const config = await program.account.config.fetch(configPda);
+      expect(await feeFor(owner, 500)).to.equal(
+        Math.floor((500 * config.airtimeFeeBps) / 10_000)
+      );
+    });
+  });
+
+  describe("notification preferences", () => {
+    const setNotifications = (
+      owner: { user: Keypair; phone: number[] },
+      enabled: boolean
+    ) =>
+      program.methods
+        .setNotifications(enabled)
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+    it("carries the user's preference in TransactionCompleted", async () => {
+      const owner = await registerUser();
+      await setNotifications(owner, true);
+      const txId = await createTransaction(owner);
+
+      let listener: number;
+      const event = new Promise<any>((resolve) => {
+        listener = program.addEventListener("transactionCompleted", (e) => {
+          if (Buffer.from(e.txId).equals(Buffer.from(txId))) resolve(e);
+        });
+      });
+      await completeTransaction(owner, txId);
+      const completed = await event;
+      await program.removeEventListener(listener);
+
+      expect(completed.notificationsEnabled).to.be.true;
+    });
+
+    it("can only be changed by the user's wallet", async () => {
+      const owner = await registerUser();
+      const stranger = await fundedKeypair();
+      await expectError(
+        program.methods
+          .setNotifications(true)
+          .accountsPartial({
+            userAccount: userPda(owner.phone),
+            user: stranger.publicKey,
+          })
+          .signers([stranger])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("max_limit timelock", () => {
+    const setTimelock = (seconds: number) =>
+      program.methods
+        .setLimitTimelock(new BN(seconds))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const setMaxLimit = async (maxLimit: number) =>
+      program.methods
+        .updateConfig(50, 100, new BN(100), new BN(maxLimit))
+        .accountsPartial({
+          config: configPda,
+          configSnapshot: await nextConfigSnapshot(),
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+    const applyIncrease = () =>
+      program.methods
+        .applyLimitIncrease()
+        .accountsPartial({
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    after(async () => {
+      await setTimelock(0);
+      await setMaxLimit(1_000_000);
+    });
+
+    it("holds increases until the timelock passes", async () => {
+      await setTimelock(3_600);
+      await setMaxLimit(2_000_000);
+
+      const config = await program.account.config.fetch(configPda);
+      expect(config.maxLimit.toNumber()).to.equal(1_000_000);
+      expect(config.pendingMaxLimit.toNumber()).to.equal(2_000_000);
+      await expectError(applyIncrease(), "LimitIncreaseLocked");
+    });
+
+    it("applies decreases at once and drops the pending increase", async () => {
+      await setTimelock(3_600);
+      await setMaxLimit(2_000_000);
+      await setMaxLimit(900_000);
+
+      const config = await program.account.config.fetch(configPda);
+      expect(config.maxLimit.toNumber()).to.equal(900_000);
+      expect(config.pendingMaxLimit.toNumber()).to.equal(0);
+      await expectError(applyIncrease(), "NoPendingLimitIncrease");
+    });
+
+    it("promotes the increase once unlocked", async () => {
+      await setTimelock(1);
+      await setMaxLimit(2_000_000);
+      await new Promise((resolve) => setTimeout(resolve, 2_000));
+      await applyIncrease();
+
+      const config = await program.account.config.fetch(configPda);
+      expect(config.maxLimit.toNumber()).to.equal(2_000_000);
+    });
+  });
+
+  describe("transaction priority", () => {
+    it("stores the priority hint and emits it on creation", async () => {
+      const owner = await registerUser();
+      const txId = newTxId();
+
+      let listener: number;
+      const event = new Promise<any>((resolve) => {
+        listener = program.addEventListener("transactionCreated", (e) => {
+          if (Buffer.from(e.txId).equals(Buffer.from(txId))) resolve(e);
+        });
+      });
+      await createTransaction(owner, { txId, priority: 3 });
+      const created = await event;
+      await program.removeEventListener(listener);
+
+      expect(created.priority).to.equal(3);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.priority).to.equal(3);
+    });
+
+    it("rejects priorities above the maximum", async () => {
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { priority: 4 }),
+        "InvalidPriority"
+      );
+    });
+  });
+
+  describe("transfer_volume", () => {
+    const transferVolume = (
+      from: { phone: number[] },
+      to: { phone: number[] },
+      amount: number
+    ) =>
+      program.methods
+        .transferVolume(new BN(amount))
+        .accountsPartial({
+          fromUser: userPda(from.phone),
+          toUser: userPda(to.phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+    const volumeOf = async (owner: { phone: number[] }) =>
+      (
+        await program.account.userAccount.fetch(userPda(owner.phone))
+      ).totalVolume.toNumber();
+
+    it("moves volume between users", async () => {
+      const from = await registerUser();
+      const to = await registerUser();
+      const txId = await createTransaction(from, { amountNgn: 1_000 });
+      await completeTransaction(from, txId);
+
+      await transferVolume(from, to, 400);
+      expect(await volumeOf(from)).to.equal(600);
+      expect(await volumeOf(to)).to.equal(400);
+    });
+
+    it("rejects transfers above the source volume", async () => {
+      const from = await registerUser();
+      const to = await registerUser();
+      await expectError(transferVolume(from, to, 1), "VolumeUnderflow");
+    });
+
+    it("rejects transferring to the same user", async () => {
+      const owner = await registerUser();
+      await expectError(transferVolume(owner, owner, 0), "SameUserAccount");
+    });
+  });
+
+  describe("display metadata", () => {
+    const setDisplayMetadata = (symbol: string, ngn: number, usdc: number) =>
+      program.methods
+        .setDisplayMetadata(
+          Array.from(Buffer.concat([Buffer.from(symbol)], 4)),
+          ngn,
+          usdc
+        )
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const getConfig = () =>
+      program.methods
+        .getConfig()
+        .accountsPartial({ config: configPda })
+        .view();
+
+    after(() => setDisplayMetadata("NGN", 2, 6));
+
+    it("defaults to kobo and six usdc decimals", async () => {
+      const config = await getConfig();
+      expect(Buffer.from(config.ngnSymbol).toString()).to.equal("NGN\0");
+      expect(config.ngnDecimals).to.equal(2);
+      expect(config.usdcDecimals).to.equal(6);
+    });
+
+    it("returns updated metadata from get_config", async () => {
+      await setDisplayMetadata("₦", 2, 6);
+      const config = await getConfig();
+      expect(
+        Buffer.from(config.ngnSymbol).toString().replace(/\0+$/, "")
+      ).to.equal("₦");
+    });
+  });
+
+  describe("backup admin", () => {
+    const setBackupAdmin = (
+      currentAdmin: Keypair | null,
+      backupAdmin: PublicKey,
+      timeout: number
+    ) =>
+      program.methods
+        .setBackupAdmin(backupAdmin, new BN(timeout))
+        .accountsPartial({
+          config: configPda,
+          admin: currentAdmin?.publicKey ?? admin.publicKey,
+        })
+        .signers(currentAdmin ? [currentAdmin] : [])
+        .rpc();
+    const claim = (backup: Keypair | null) =>
+      program.methods
+        .claimByBackup()
+        .accountsPartial({
+          config: configPda,
+          backupAdmin: backup?.publicKey ?? admin.publicKey,
+        })
+        .signers(backup ? [backup] : [])
+        .rpc();
+    const sleep = (ms: number) =>
+      new Promise((resolve) => setTimeout(resolve, ms));
+
+    after(() => setBackupAdmin(null, PublicKey.default, 0));
+
+    it("rejects a claim while the admin is active", async () => {
+      const backup = await fundedKeypair();
+      await setBackupAdmin(null, backup.publicKey, 3_600);
+      await expectError(claim(backup), "AdminStillActive");
+    });
+
+    it("rejects a claim from anyone but the backup", async () => {
+      const backup = await fundedKeypair();
+      const stranger = await fundedKeypair();
+      await setBackupAdmin(null, backup.publicKey, 1);
+      await sleep(2_000);
+      await expectError(claim(stranger), "Unauthorized");
+    });
+
+    it("promotes the backup once the admin times out", async () => {
+      const backup = await fundedKeypair();
+      await setBackupAdmin(null, backup.publicKey, 1);
+      await sleep(2_000);
+      await claim(backup);
+
+      try {
+        const config = await program.account.config.fetch(configPda);
+        expect(config.admin.toBase58()).to.equal(backup.publicKey.toBase58());
+        expect(config.backupAdmin.toBase58()).to.equal(
+          PublicKey.default.toBase58()
+        );
+      } finally {
+        // Hand governance back to the suite's admin the same way
+        await setBackupAdmin(backup, admin.publicKey, 1);
+        await sleep(2_000);
+        await claim(null);
+      }
+    });
+  });
+
+  describe("amount bucketing", () => {
+    const setBucketSize = (bucketSize: number) =>
+      program.methods
+        .setBucketSize(new BN(bucketSize))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const optIn = (owner: { user: Keypair; phone: number[] }) =>
+      program.methods
+        .setBucketed(true)
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+    before(() => setBucketSize(500));
+    after(() => setBucketSize(0));
+
+    it("rounds stored amounts to the nearest bucket", async () => {
+      const owner = await registerUser();
+      await optIn(owner);
+
+      const down = await createTransaction(owner, { amountNgn: 1_249 });
+      const up = await createTransaction(owner, { amountNgn: 1_250 });
+      const downTx = await program.account.transactionAccount.fetch(
+        txPda(down)
+      );
+      const upTx = await program.account.transactionAccount.fetch(txPda(up));
+      expect(downTx.amountNgn.toNumber()).to.equal(1_000);
+      expect(upTx.amountNgn.toNumber()).to.equal(1_500);
+    });
+
+    it("computes the fee on the exact amount", async () => {
+      const owner = await registerUser();
+      await optIn(owner);
+      const config = await program.account.config.fetch(configPda);
+
+      const txId = await createTransaction(owner, { amountNgn: 1_249 });
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.fee.toNumber()).to.equal(
+        Math.floor((1_249 * config.airtimeFeeBps) / 10_000)
+      );
+    });
+
+    it("accrues the bucketed amount as volume", async () => {
+      const owner = await registerUser();
+      await optIn(owner);
+      const txId = await createTransaction(owner, { amountNgn: 1_249 });
+      await completeTransaction(owner, txId);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalVolume.toNumber()).to.equal(1_000);
+    });
+
+    it("leaves users who have not opted in untouched", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 1_249 });
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.amountNgn.toNumber()).to.equal(1_249);
+    });
+  });
+
+  describe("linked token accounts", () => {
+    const TOKEN_PROGRAM_ID = new PublicKey(
+      "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"
+    );
+    const ASSOCIATED_TOKEN_PROGRAM_ID = new PublicKey(
+      "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL"
+    );
+    const usdcMint = Keypair.generate().publicKey;
+    const ataOf = (wallet: PublicKey) =>
+      PublicKey.findProgramAddressSync(
+        [wallet.toBuffer(), TOKEN_PROGRAM_ID.toBuffer(), usdcMint.toBuffer()],
+        ASSOCIATED_TOKEN_PROGRAM_ID
+      )[0];
+    const setUsdcMint = (mint: PublicKey) =>
+      program.methods
+        .setUsdcMint(mint)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const link = (
+      owner: { user: Keypair; phone: number[] },
+      tokenAccount: PublicKey
+    ) =>
+      program.methods
+        .linkTokenAccount(tokenAccount)
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+    before(() => setUsdcMint(usdcMint));
+    after(() => setUsdcMint(PublicKey.default));
+
+    it("stores the wallet's associated token account", async () => {
+      const owner = await registerUser();
+      await link(owner, ataOf(owner.user.publicKey));
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.tokenAccount.toBase58()).to.equal(
+        ataOf(owner.user.publicKey).toBase58()
+      );
+    });
+
+    it("rejects a token account of another wallet", async () => {
+      const owner = await registerUser();
+      const other = Keypair.generate().publicKey;
+      await expectError(link(owner, ataOf(other)), "InvalidTokenAccount");
+    });
+  });
+
+  describe("micro volume", () => {
+    const setMinVolumeAccrual = (amount: number) =>
+      program.methods
+        .setMinVolumeAccrual(new BN(amount))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const completeAmount = async (
+      owner: { user: Keypair; phone: number[] },
+      amountNgn: number
+    ) => {
+      const txId = await createTransaction(owner, { amountNgn });
+      await completeTransaction(owner, txId);
+    };
+
+    before(() => setMinVolumeAccrual(500));
+    after(() => setMinVolumeAccrual(0));
+
+    it("keeps micro transactions out of total_volume", async () => {
+      const owner = await registerUser();
+      for (let i = 0; i < 3; i++) {
+        await completeAmount(owner, 499);
+      }
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalVolume.toNumber()).to.equal(0);
+      expect(user.microVolume.toNumber()).to.equal(3 * 499);
+    });
+
+    it("accrues transactions at the threshold normally", async () => {
+      const owner = await registerUser();
+      await completeAmount(owner, 500);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalVolume.toNumber()).to.equal(500);
+      expect(user.microVolume.toNumber()).to.equal(0);
+    });
+  });
+
+  describe("failure rebates", () => {
+    const setRebateBps = (bps: number) =>
+      program.methods
+        .setFailureRebateBps(bps)
+        .accountsPartial({
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+    const setEnforced = (enforced: boolean) =>
+      program.methods
+        .setBalanceEnforced(enforced)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const fail = (owner: { user: Keypair; phone: number[] }, txId: number[]) =>
+      program.methods
+        .failTransaction()
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    before(() => setRebateBps(5_000));
+    after(async () => {
+      await setRebateBps(0);
+      await setEnforced(false);
+    });
+
+    it("refunds the rebate of a held fee to the balance", async () => {
+      await setEnforced(true);
+      const owner = await registerUser();
+      await program.methods
+        .creditBalance(new BN(2_020))
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+
+      // 2,000 at 100 bps carries a 20 fee, half of which comes back
+      const txId = await createTransaction(owner, { amountNgn: 2_000 });
+      await fail(owner, txId);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(user.availableBalance.toNumber()).to.equal(2_000 + 10);
+      expect(tx.feeRebate.toNumber()).to.equal(10);
+      expect(tx.debitedAmount.toNumber()).to.equal(10);
+      await setEnforced(false);
+    });
+
+    it("records the rebate as owed when no fee was held", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 2_000 });
+      await fail(owner, txId);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.rebateOwed.toNumber()).to.equal(10);
+      expect(user.availableBalance.toNumber()).to.equal(0);
+    });
+
+    it("rounds the rebate down", async () => {
+      const owner = await registerUser();
+      // 1,500 at 100 bps carries a 15 fee; half is 7.5
+      const txId = await createTransaction(owner, { amountNgn: 1_500 });
+      await fail(owner, txId);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.rebateOwed.toNumber()).to.equal(7);
+    });
+
+    it("takes the rebate back when the transaction is retried", async () => {
+      const setMaxRetries = (maxRetries: number) =>
+        program.methods
+          .setMaxRetries(maxRetries)
+          .accountsPartial({ config: configPda, admin: admin.publicKey })
+          .rpc();
+      await setEnforced(true);
+      await setMaxRetries(1);
+      try {
+        const owner = await registerUser();
+        await program.methods
+          .creditBalance(new BN(2_020))
+          .accountsPartial({
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            admin: admin.publicKey,
+          })
+          .rpc();
+        const txId = await createTransaction(owner, { amountNgn: 2_000 });
+        await fail(owner, txId);
+        await program.methods
+          .retryTransaction()
+          .accountsPartial({
+            transactionAccount: txPda(txId),
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            role: null,
+            authority: admin.publicKey,
+          })
+          .rpc();
+
+        // Back to what creation held: the amount and the whole fee
+        const user = await program.account.userAccount.fetch(
+          userPda(owner.phone)
+        );
+        const tx = await program.account.transactionAccount.fetch(txPda(txId));
+        expect(user.availableBalance.toNumber()).to.equal(0);
+        expect(tx.debitedAmount.toNumber()).to.equal(2_020);
+        expect(tx.feeRebate.toNumber()).to.equal(0);
+      } finally {
+        await setMaxRetries(0);
+        await setEnforced(false);
+      }
+    });
+  });
+
+  describe("config snapshots", () => {
+    const updateFees = async (airtimeFeeBps: number) =>
+      program.methods
+        .updateConfig(50, airtimeFeeBps, new BN(100), new BN(1_000_000))
+        .accountsPartial({
+          config: configPda,
+          configSnapshot: await nextConfigSnapshot(),
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    after(() => updateFees(100));
+
+    it("records the initial config as epoch zero", async () => {
+      const snapshot = await program.account.configSnapshot.fetch(
+        configSnapshotPda(0)
+      );
+      expect(snapshot.epoch.toNumber()).to.equal(0);
+      expect(snapshot.airtimeFeeBps).to.equal(100);
+    });
+
+    it("opens a new epoch on each update_config", async () => {
+      const previous = await program.account.config.fetch(configPda);
+      await updateFees(200);
+
+      const config = await program.account.config.fetch(configPda);
+      const epoch = config.configEpoch.toNumber();
+      expect(epoch).to.equal(previous.configEpoch.toNumber() + 1);
+      const snapshot = await program.account.configSnapshot.fetch(
+        configSnapshotPda(epoch)
+      );
+      expect(snapshot.airtimeFeeBps).to.equal(200);
+    });
+
+    it("tags transactions with the epoch they were created under", async () => {
+      const owner = await registerUser();
+      await updateFees(150);
+      const txId = await createTransaction(owner, { amountNgn: 2_000 });
+
+      const config = await program.account.config.fetch(configPda);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.configEpoch.toNumber()).to.equal(config.configEpoch.toNumber());
+      const snapshot = await program.account.configSnapshot.fetch(
+        configSnapshotPda(tx.configEpoch.toNumber())
+      );
+      expect(tx.appliedFeeBps).to.equal(snapshot.airtimeFeeBps);
+    });
+  });
+
+  describe("suspended withdrawals", () => {
+    const setAllowed = (allowed: boolean) =>
+      program.methods
+        .setAllowSuspendedWithdrawal(allowed)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const fundedUser = async () => {
+      const owner = await registerUser();
+      await program.methods
+        .creditBalance(new BN(5_000))
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+      return owner;
+    };
+    const suspend = (owner: { phone: number[] }) =>
+      program.methods
+        .suspendUser()
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+    const withdraw = (owner: { user: Keypair; phone: number[] }) =>
+      createTransaction(owner, {
+        txType: { withdrawal: {} },
+        amountNgn: 2_000,
+      });
+
+    after(() => setAllowed(false));
+
+    it("pays withdrawals out of the available balance", async () => {
+      const owner = await fundedUser();
+      const txId = await withdraw(owner);
+      await completeTransaction(owner, txId);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.availableBalance.toNumber()).to.equal(3_000);
+    });
+
+    it("blocks suspended users entirely by default", async () => {
+      const owner = await fundedUser();
+      await suspend(owner);
+      await expectError(withdraw(owner), "UserSuspended");
+    });
+
+    it("lets suspended users withdraw but not buy when allowed", async () => {
+      await setAllowed(true);
+      const owner = await fundedUser();
+      await suspend(owner);
+
+      await withdraw(owner);
+      await expectError(createTransaction(owner), "UserSuspended");
+      await expectError(
+        createTransaction(owner, {
+          txType: { crypto: {} },
+          amountUsdc: 1_300_000,
+        }),
+        "UserSuspended"
+      );
+    });
+  });
+
+  describe("guardian co-signing", () => {
+    const setThreshold = (threshold: number) =>
+      program.methods
+        .setDualSignThreshold(new BN(threshold))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const setGuardian = (
+      owner: { user: Keypair; phone: number[] },
+      guardian: PublicKey | null
+    ) =>
+      program.methods
+        .setGuardian(guardian)
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+    before(() => setThreshold(5_000));
+    after(() => setThreshold(0));
+
+    it("requires the guardian above the threshold", async () => {
+      const owner = await registerUser();
+      const guardian = Keypair.generate();
+      await setGuardian(owner, guardian.publicKey);
+
+      await expectError(
+        createTransaction(owner, { amountNgn: 5_001 }),
+        "GuardianSignatureRequired"
+      );
+      await createTransaction(owner, { amountNgn: 5_001, guardian });
+    });
+
+    it("rejects a co-signer that is not the guardian", async () => {
+      const owner = await registerUser();
+      await setGuardian(owner, Keypair.generate().publicKey);
+      await expectError(
+        createTransaction(owner, {
+          amountNgn: 5_001,
+          guardian: Keypair.generate(),
+        }),
+        "GuardianSignatureRequired"
+      );
+    });
+
+    it("only needs the user at or below the threshold", async () => {
+      const owner = await registerUser();
+      await setGuardian(owner, Keypair.generate().publicKey);
+      await createTransaction(owner, { amountNgn: 5_000 });
+    });
+
+    it("leaves users without a guardian unaffected", async () => {
+      const owner = await registerUser();
+      await createTransaction(owner, { amountNgn: 5_001 });
+    });
+  });
+
+  describe("environments", () => {
+    const stagingPda = PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), Buffer.from([1])],
+      program.programId
+    )[0];
+    const stagingSnapshotPda = PublicKey.findProgramAddressSync(
+      [
+        Buffer.from("config_snapshot"),
+        Buffer.from([1]),
+        new BN(0).toArrayLike(Buffer, "le", 8),
+      ],
+      program.programId
+    )[0];
+
+    it("keeps a separate config per env_id", async () => {
+      await program.methods
+        .initializeConfig(10, 20, new BN(1), new BN(5_000), 1)
+        .accountsPartial({
+          config: stagingPda,
+          configSnapshot: stagingSnapshotPda,
+          defaultConfig: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+      await program.methods
+        .setFreeTxCount(7)
+        .accountsPartial({ config: stagingPda, admin: admin.publicKey })
+        .rpc();
+
+      const staging = await program.account.config.fetch(stagingPda);
+      const production = await program.account.config.fetch(configPda);
+      expect(staging.envId).to.equal(1);
+      expect(staging.freeTxCount).to.equal(7);
+      expect(staging.cryptoFeeBps).to.equal(10);
+      expect(production.envId).to.equal(0);
+      expect(production.freeTxCount).to.equal(0);
+    });
+
+    it("lets only the default config's admin add an env", async () => {
+      const outsider = await fundedKeypair();
+      const [envPda] = PublicKey.findProgramAddressSync(
+        [Buffer.from("config"), Buffer.from([2])],
+        program.programId
+      );
+      const [envSnapshotPda] = PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("config_snapshot"),
+          Buffer.from([2]),
+          new BN(0).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      );
+      const initialize = (defaultConfig: PublicKey | null) =>
+        program.methods
+          .initializeConfig(10, 20, new BN(1), new BN(5_000), 2)
+          .accountsPartial({
+            config: envPda,
+            configSnapshot: envSnapshotPda,
+            defaultConfig,
+            admin: outsider.publicKey,
+          })
+          .signers([outsider])
+          .rpc();
+
+      await expectError(initialize(configPda), "Unauthorized");
+      await expectError(initialize(null), "Unauthorized");
+    });
+  });
+
+  describe("is_tx_id_used", () => {
+    const isTxIdUsed = (txId: number[], account: PublicKey) =>
+      program.methods
+        .isTxIdUsed(txId)
+        .remainingAccounts([
+          { pubkey: account, isSigner: false, isWritable: false },
+        ])
+        .view();
+
+    it("reports whether a transaction exists for the id", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      const freeId = newTxId();
+
+      expect(await isTxIdUsed(txId, txPda(txId))).to.equal(true);
+      expect(await isTxIdUsed(freeId, txPda(freeId))).to.equal(false);
+    });
+
+    it("rejects an account that is not the transaction PDA", async () => {
+      await expectError(
+        program.methods
+          .isTxIdUsed(newTxId())
+          .remainingAccounts([
+            { pubkey: configPda, isSigner: false, isWritable: false },
+          ])
+          .rpc(),
+        "InvalidTransactionAccount"
+      );
+    });
+  });
+
+  describe("risk approval", () => {
+    const approver = Keypair.generate();
+    const setRiskApprover = (key: PublicKey, threshold: number) =>
+      program.methods
+        .setRiskApprover(key, new BN(threshold))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    // Ed25519Program lays out the signature at bytes 48..112
+    const provideApproval = (
+      txId: number[],
+      nonce: number[],
+      signer: Keypair
+    ) => {
+      const verify = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: signer.secretKey,
+        message: Buffer.concat([Buffer.from(txId), Buffer.from(nonce)]),
+      });
+      return program.methods
+        .provideApproval(nonce, Array.from(verify.data.subarray(48, 112)))
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          config: configPda,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        })
+        .preInstructions([verify])
+        .rpc();
+    };
+
+    before(() => setRiskApprover(approver.publicKey, 5_000));
+    after(() => setRiskApprover(PublicKey.default, 0));
+
+    it("holds a flagged transaction until the approver signs", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 10_000 });
+      const { approvalNonce } = await program.account.transactionAccount.fetch(
+        txPda(txId)
+      );
+      expect(approvalNonce.some((byte) => byte !== 0)).to.equal(true);
+
+      await expectError(completeTransaction(owner, txId), "ApprovalRequired");
+      await provideApproval(txId, approvalNonce, approver);
+      await completeTransaction(owner, txId);
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.approved).to.equal(true);
+      expect(tx.status).to.deep.equal({ completed: {} });
+    });
+
+    it("does not flag transactions at or below the threshold", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 5_000 });
+      await completeTransaction(owner, txId);
+    });
+
+    it("rejects a signature from another key", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 10_000 });
+      const { approvalNonce } = await program.account.transactionAccount.fetch(
+        txPda(txId)
+      );
+
+      await expectError(
+        provideApproval(txId, approvalNonce, Keypair.generate()),
+        "InvalidSignature"
+      );
+    });
+  });
+
+  describe("spending categories", () => {
+    const DATA = 1;
+    const setCategoryLimit = (
+      owner: { user: Keypair; phone: number[] },
+      category: number,
+      weeklyLimit: number
+    ) =>
+      program.methods
+        .setCategoryLimit(category, new BN(weeklyLimit))
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          userBudget: budgetPda(owner.phone),
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+    it("caps weekly spend in a category", async () => {
+      const owner = await registerUser();
+      await setCategoryLimit(owner, DATA, 5_000);
+
+      await createTransaction(owner, { amountNgn: 3_000, category: DATA });
+      await expectError(
+        createTransaction(owner, { amountNgn: 3_000, category: DATA }),
+        "CategoryLimitExceeded"
+      );
+      // Other categories and uncategorized spend are unaffected
+      await createTransaction(owner, { amountNgn: 3_000, category: 2 });
+      await createTransaction(owner, { amountNgn: 3_000 });
+
+      const budget = await program.account.userBudget.fetch(
+        budgetPda(owner.phone)
+      );
+      const limit = budget.limits.find((slot) => slot.category === DATA);
+      expect(limit.spent.toNumber()).to.equal(3_000);
+    });
+
+    it("removes a category with a zero limit", async () => {
+      const owner = await registerUser();
+      await setCategoryLimit(owner, DATA, 1_000);
+      await setCategoryLimit(owner, DATA, 0);
+
+      await createTransaction(owner, { amountNgn: 3_000, category: DATA });
+    });
+  });
+
+  describe("migrate_config", () => {
+    it("stamps new configs at the current version", async () => {
+      const config = await program.account.config.fetch(configPda);
+      expect(config.configVersion).to.equal(20);
+    });
+
+    it("refuses to migrate a config twice", async () => {
+      await expectError(
+        program.methods
+          .migrateConfig(0)
+          .accountsPartial({ config: configPda, admin: admin.publicKey })
+          .rpc(),
+        "ConfigAlreadyMigrated"
+      );
+    });
+
+    it("rejects a signer that is not the admin", async () => {
+      const outsider = await fundedKeypair();
+      await expectError(
+        program.methods
+          .migrateConfig(0)
+          .accountsPartial({ config: configPda, admin: outsider.publicKey })
+          .signers([outsider])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("event emission", () => {
+    const setEventEmission = (crypto: boolean, airtime: boolean) =>
+      program.methods
+        .setEventEmission(crypto, airtime)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    after(() => setEventEmission(true, true));
+
+    it("skips lifecycle events for a disabled type", async () => {
+      await setEventEmission(true, false);
+      const owner = await registerUser();
+      const txId = newTxId();
+
+      const seen: string[] = [];
+      const match = (e: any) => Buffer.from(e.txId).equals(Buffer.from(txId));
+      const listeners = [
+        program.addEventListener("transactionCreated", (e) => {
+          if (match(e)) seen.push("created");
+        }),
+        program.addEventListener("transactionCompleted", (e) => {
+          if (match(e)) seen.push("completed");
+        }),
+      ];
+      await createTransaction(owner, { txId });
+      await completeTransaction(owner, txId);
+      await new Promise((resolve) => setTimeout(resolve, 1_000));
+      await Promise.all(listeners.map((l) => program.removeEventListener(l)));
+
+      expect(seen).to.be.empty;
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ completed: {} });
+    });
+  });
+
+  describe("reclaim_reservation", () => {
+    const reclaim = (txId: number[], owner: PublicKey) =>
+      program.methods
+        .reclaimReservation()
+        .accountsPartial({ reservation: reservationPda(txId), owner })
+        .rpc();
+
+    it("leaves a live reservation alone", async () => {
+      const owner = await registerUser();
+      const txId = newTxId();
+      await reserveTxId(owner.user, txId).rpc();
+
+      await expectError(
+        reclaim(txId, owner.user.publicKey),
+        "ReservationStillActive"
+      );
+    });
+
+    it("only pays the rent back to the reservation owner", async () => {
+      const owner = await registerUser();
+      const txId = newTxId();
+      await reserveTxId(owner.user, txId).rpc();
+
+      await expectError(reclaim(txId, admin.publicKey), "Unauthorized");
+    });
+  });
+
+  describe("fee bounds", () => {
+    const setFeeBounds = (min: number, max: number) =>
+      program.methods
+        .setFeeBounds(new BN(min), new BN(max))
+        .accountsPartial({
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+    const feeOf = async (txId: number[]) =>
+      (await program.account.transactionAccount.fetch(txPda(txId))).fee;
+
+    after(() => setFeeBounds(0, 0));
+
+    it("raises a percentage fee below the floor", async () => {
+      await setFeeBounds(50, 0);
+      const owner = await registerUser();
+      // 1% of 1,000 is 10, under the floor
+      const txId = await createTransaction(owner, { amountNgn: 1_000 });
+      expect((await feeOf(txId)).toNumber()).to.equal(50);
+    });
+
+    it("caps the fee at the maximum", async () => {
+      await setFeeBounds(50, 60);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 10_000 });
+      expect((await feeOf(txId)).toNumber()).to.equal(60);
+    });
+
+    it("rejects a floor above the cap", async () => {
+      await expectError(setFeeBounds(100, 50), "InvalidConfig");
+    });
+  });
+
+  describe("get_balances_batch", () => {
+    const asRemaining = (keys: PublicKey[]) =>
+      keys.map((pubkey) => ({ pubkey, isSigner: false, isWritable: false }));
+
+    it("reads every user passed in", async () => {
+      const first = await registerUser();
+      const second = await registerUser();
+      await forceComplete(first, await createTransaction(first));
+
+      const entries = await program.methods
+        .getBalancesBatch()
+        .remainingAccounts(
+          asRemaining([userPda(first.phone), userPda(second.phone)])
+        )
+        .view();
+
+      expect(entries).to.have.length(2);
+      expect(entries[0].phone).to.deep.equal(first.phone);
+      expect(entries[0].totalVolume.toNumber()).to.equal(1_000);
+      expect(entries[1].phone).to.deep.equal(second.phone);
+      expect(entries[1].totalVolume.toNumber()).to.equal(0);
+      expect(entries[1].status).to.deep.equal({ active: {} });
+    });
+
+    it("rejects accounts that are not user accounts", async () => {
+      await expectError(
+        program.methods
+          .getBalancesBatch()
+          .remainingAccounts(asRemaining([configPda]))
+          .rpc(),
+        "AccountDiscriminatorMismatch"
+      );
+    });
+  });
+
+  describe("change_pin", () => {
+    const setCooldown = (seconds: number) =>
+      program.methods
+        .setMinPinChangeInterval(new BN(seconds))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const changePin = (
+      owner: { user: Keypair; phone: number[] },
+      current: number,
+      next: number
+    ) =>
+      program.methods
+        .changePin(Array(32).fill(current), Array(32).fill(next))
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+    after(() => setCooldown(0));
+
+    it("rejects a negative cooldown", async () => {
+      await expectError(setCooldown(-1), "InvalidConfig");
+    });
+
+    it("rejects rapid successive changes", async () => {
+      await setCooldown(3_600);
+      const owner = await registerUser();
+
+      await changePin(owner, 7, 8);
+      await expectError(changePin(owner, 8, 9), "PinChangeTooSoon");
+
+      const { pinHash } = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(pinHash).to.deep.equal(Array(32).fill(8));
+    });
+
+    it("requires the current PIN", async () => {
+      const owner = await registerUser();
+      await expectError(changePin(owner, 1, 8), "InvalidPin");
+    });
+  });
+
+  describe("client timestamps", () => {
+    it("stores a client timestamp close to the cluster clock", async () => {
+      const owner = await registerUser();
+      const clientTimestamp = Math.floor(Date.now() / 1000);
+      const txId = await createTransaction(owner, { clientTimestamp });
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.clientTimestamp.toNumber()).to.equal(clientTimestamp);
+    });
+
+    it("rejects backdated and future-dated timestamps", async () => {
+      const owner = await registerUser();
+      const now = Math.floor(Date.now() / 1000);
+      await expectError(
+        createTransaction(owner, { clientTimestamp: now - 3_600 }),
+        "TimestampSkew"
+      );
+      await expectError(
+        createTransaction(owner, { clientTimestamp: now + 3_600 }),
+        "TimestampSkew"
+      );
+    });
+  });
+
+  describe("set_display_name", () => {
+    const padded = (text: string) => {
+      const name = Buffer.alloc(32);
+      Buffer.from(text).copy(name);
+      return Array.from(name);
+    };
+    const setDisplayName = (
+      owner: { user: Keypair; phone: number[] },
+      name: number[]
+    ) =>
+      program.methods
+        .setDisplayName(name)
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+    it("stores the name trimmed", async () => {
+      const owner = await registerUser();
+      await setDisplayName(owner, padded("  Adaeze Okafor "));
+
+      const { displayName } = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(displayName).to.deep.equal(padded("Adaeze Okafor"));
+    });
+
+    it("rejects control characters", async () => {
+      const owner = await registerUser();
+      await expectError(
+        setDisplayName(owner, padded("Ada\nOkafor")),
+        "InvalidDisplayName"
+      );
+    });
+  });
+
+  describe("epoch lag", () => {
+    const setMaxEpochLag = (lag: number) =>
+      program.methods
+        .setMaxEpochLag(new BN(lag))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const bumpEpoch = async () =>
+      program.methods
+        .updateConfig(50, 100, new BN(100), new BN(1_000_000))
+        .accountsPartial({
+          config: configPda,
+          configSnapshot: await nextConfigSnapshot(),
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    after(() => setMaxEpochLag(0));
+
+    it("completes a transaction within the allowed lag", async () => {
+      await setMaxEpochLag(1);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await bumpEpoch();
+
+      await completeTransaction(owner, txId);
+    });
+
+    it("rejects a transaction from an expired epoch", async () => {
+      await setMaxEpochLag(1);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await bumpEpoch();
+      await bumpEpoch();
+
+      await expectError(completeTransaction(owner, txId), "EpochExpired");
+    });
+  });
+
+  describe("dispute limits", () => {
+    const setDisputeLimits = (max: number, window: number) =>
+      program.methods
+        .setDisputeLimits(max, new BN(window))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const completedTx = async (owner: { user: Keypair; phone: number[] }) => {
+      const txId = await createTransaction(owner);
+      await forceComplete(owner, txId);
+      return txId;
+    };
+    const dispute = (
+      owner: { user: Keypair; phone: number[] },
+      txId: number[]
+    ) =>
+      program.methods
+        .disputeTransaction()
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+    const resolve = (
+      owner: { phone: number[] },
+      txId: number[],
+      upheld: boolean
+    ) =>
+      program.methods
+        .resolveDispute(upheld)
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+    const statusOf = async (owner: { phone: number[] }) =>
+      (await program.account.userAccount.fetch(userPda(owner.phone))).status;
+
+    before(() => setDisputeLimits(2, 3_600));
+    after(() => setDisputeLimits(0, 0));
+
+    it("suspends a user once disputes exceed the limit", async () => {
+      const owner = await registerUser();
+      const txIds = [
+        await completedTx(owner),
+        await completedTx(owner),
+        await completedTx(owner),
+      ];
+
+      await dispute(owner, txIds[0]);
+      await dispute(owner, txIds[1]);
+      expect(await statusOf(owner)).to.deep.equal({ active: {} });
+
+      await dispute(owner, txIds[2]);
+      expect(await statusOf(owner)).to.deep.equal({ suspended: {} });
+    });
+
+    it("stops counting a rejected dispute", async () => {
+      const owner = await registerUser();
+      const txIds = [
+        await completedTx(owner),
+        await completedTx(owner),
+        await completedTx(owner),
+      ];
+
+      await dispute(owner, txIds[0]);
+      await dispute(owner, txIds[1]);
+      await resolve(owner, txIds[1], false);
+      await dispute(owner, txIds[2]);
+
+      expect(await statusOf(owner)).to.deep.equal({ active: {} });
+      const tx = await program.account.transactionAccount.fetch(
+        txPda(txIds[1])
+      );
+      expect(tx.status).to.deep.equal({ completed: {} });
+    });
+
+    it("keeps counting an upheld dispute", async () => {
+      const owner = await registerUser();
+      const txIds = [
+        await completedTx(owner),
+        await completedTx(owner),
+        await completedTx(owner),
+      ];
+
+      await dispute(owner, txIds[0]);
+      await dispute(owner, txIds[1]);
+      await resolve(owner, txIds[1], true);
+      await dispute(owner, txIds[2]);
+
+      expect(await statusOf(owner)).to.deep.equal({ suspended: {} });
+    });
+
+    it("rejects a negative window", async () => {
+      await expectError(setDisputeLimits(2, -1), "InvalidConfig");
+    });
+  });
+
+  describe("get_recommended_compute_units", () => {
+    const recommend = (txType: any, amount: number) =>
+      program.methods
+        .getRecommendedComputeUnits(txType, new BN(amount))
+        .view();
+
+    it("adds headroom as the amount crosses each tier", async () => {
+      expect(await recommend({ airtime: {} }, 1_000)).to.equal(40_000);
+      expect(await recommend({ airtime: {} }, 100_000)).to.equal(50_000);
+      expect(await recommend({ crypto: {} }, 10_000_000)).to.equal(85_000);
+    });
+  });
+
+  describe("register_and_transact", () => {
+    // Reserves the id in the same transaction unless `reserve` is false
+    const registerAndTransact = async (
+      phone: number[],
+      txId: number[],
+      amountNgn: number,
+      reserve = true
+    ) => {
+      const user = await fundedKeypair();
+      const preInstructions = reserve
+        ? [await reserveTxId(user, txId).instruction()]
+        : [];
+      return program.methods
+        .registerAndTransact(
+          phone,
+          Array(32).fill(7),
+          txId,
+          { airtime: {} },
+          new BN(amountNgn),
+          null
+        )
+        .accountsPartial({
+          userAccount: userPda(phone),
+          transactionAccount: txPda(txId),
+          reservation: reservationPda(txId),
+          config: configPda,
+          user: user.publicKey,
+        })
+        .preInstructions(preInstructions)
+        .signers([user])
+        .rpc();
+    };
+
+    it("registers the user and opens the first transaction", async () => {
+      const phone = nextPhone();
+      const txId = newTxId();
+      await registerAndTransact(phone, txId, 1_000);
+
+      const user = await program.account.userAccount.fetch(userPda(phone));
+      expect(user.txCount.toNumber()).to.equal(1);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.userPhone).to.deep.equal(phone);
+      expect(tx.status).to.deep.equal({ pending: {} });
+    });
+
+    it("leaves no user behind when the transaction fails", async () => {
+      const phone = nextPhone();
+      await expectError(
+        registerAndTransact(phone, newTxId(), 10_000_000),
+        "TransactionLimitOutOfBounds"
+      );
+
+      const info = await provider.connection.getAccountInfo(userPda(phone));
+      expect(info).to.be.null;
+    });
+
+    it("can't take an id reserved by another wallet", async () => {
+      const holder = await registerUser();
+      const txId = newTxId();
+      await reserveTxId(holder.user, txId).rpc();
+
+      await expectError(
+        registerAndTransact(nextPhone(), txId, 1_000, false),
+        "Unauthorized"
+      );
+    });
+
+    it("is refused while risk checks are on", async () => {
+      const setRiskChecks = (enabled: boolean) =>
+        program.methods
+          .setRiskChecksEnabled(enabled)
+          .accountsPartial({ config: configPda, admin: admin.publicKey })
+          .rpc();
+
+      await setRiskChecks(true);
+      try {
+        await expectError(
+          registerAndTransact(nextPhone(), newTxId(), 1_000),
+          "RiskCheckFailed"
+        );
+      } finally {
+        await setRiskChecks(false);
+      }
+    });
+  });
+
+  describe("monotonic timestamps", () => {
+    it("records the latest transaction time on the user", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+
+      const { lastTxTimestamp } = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(lastTxTimestamp.toNumber()).to.equal(tx.timestamp.toNumber());
+    });
+  });
+
+  describe("limit multiplier", () => {
+    const setLimitMultiplier = (bps: number) =>
+      program.methods
+        .setLimitMultiplier(bps)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    after(() => setLimitMultiplier(10_000));
+
+    it("raises the ceiling above 1x", async () => {
+      await setLimitMultiplier(15_000);
+      const owner = await registerUser();
+      await createTransaction(owner, { amountNgn: 1_400_000 });
+    });
+
+    it("lowers the ceiling below 1x", async () => {
+      await setLimitMultiplier(5_000);
+      const owner = await registerUser();
+      await createTransaction(owner, { amountNgn: 500_000 });
+      await expectError(
+        createTransaction(owner, { amountNgn: 600_000 }),
+        "TransactionLimitOutOfBounds"
+      );
+    });
+
+    it("rejects a zero multiplier", async () => {
+      await expectError(setLimitMultiplier(0), "InvalidConfig");
+    });
+  });
+
+  describe("risk checks", () => {
+    const riskEngine = Keypair.generate();
+    const nonce = Array(16).fill(9);
+    const setRiskChecks = async (enabled: boolean) => {
+      await program.methods
+        .setRiskApprover(
+          enabled ? riskEngine.publicKey : PublicKey.default,
+          new BN(0)
+        )
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+      await program.methods
+        .setRiskChecksEnabled(enabled)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    };
+
+    before(() => setRiskChecks(true));
+    after(() => setRiskChecks(false));
+
+    it("accepts a nonce signed by the risk engine", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, {
+        riskNonce: nonce,
+        riskSigner: riskEngine,
+      });
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.riskNonce).to.deep.equal(nonce);
+    });
+
+    it("rejects a missing or foreign signature", async () => {
+      const owner = await registerUser();
+      await expectError(createTransaction(owner), "RiskCheckFailed");
+      await expectError(
+        createTransaction(owner, {
+          riskNonce: nonce,
+          riskSigner: Keypair.generate(),
+        }),
+        "RiskCheckFailed"
+      );
+    });
+  });
+
+  describe("estimate_settlement_time", () => {
+    it("averages latency over completed transactions", async () => {
+      const before = await program.account.config.fetch(configPda);
+      const owner = await registerUser();
+      await completeTransaction(owner, await createTransaction(owner));
+
+      const after = await program.account.config.fetch(configPda);
+      expect(after.settledCount.toNumber()).to.equal(
+        before.settledCount.toNumber() + 1
+      );
+      const estimate = await program.methods
+        .estimateSettlementTime()
+        .accountsPartial({ config: configPda })
+        .view();
+      expect(estimate.toNumber()).to.equal(
+        Math.floor(
+          after.totalSettlementSeconds.toNumber() /
+            after.settledCount.toNumber()
+        )
+      );
+    });
+  });
+
+  describe("preserve_on_close", () => {
+    const setPreserve = (on: boolean) =>
+      program.methods
+        .setPreserveOnClose(on)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const archivePda = (phone: number[]) =>
+      PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("archive"),
+          createHash("sha256").update(Buffer.from(phone)).digest(),
+        ],
+        program.programId
+      )[0];
+
+    after(() => setPreserve(false));
+
+    it("restores volume when a closed phone registers again", async () => {
+      await setPreserve(true);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 1_000 });
+      await completeTransaction(owner, txId);
+      const before = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+
+      await program.methods
+        .closeUser()
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          userArchive: archivePda(owner.phone),
+          config: configPda,
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+      await program.methods
+        .registerUser(owner.phone, Array(32).fill(7))
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          userArchive: archivePda(owner.phone),
+          config: configPda,
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalVolume.toNumber()).to.equal(1_000);
+      expect(user.registeredAt.eq(before.registeredAt)).to.be.true;
+      expect(
+        await program.account.userArchive.fetchNullable(
+          archivePda(owner.phone)
+        )
+      ).to.be.null;
+    });
+
+    it("requires the archive account while enabled", async () => {
+      await setPreserve(true);
+      const owner = await registerUser();
+      await expectError(
+        program.methods
+          .closeUser()
+          .accountsPartial({
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            user: owner.user.publicKey,
+          })
+          .signers([owner.user])
+          .rpc(),
+        "UserArchiveRequired"
+      );
+    });
+  });
+
+  describe("referrer fee share", () => {
+    const setShare = (bps: number) =>
+      program.methods
+        .setReferrerFeeShareBps(bps)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const setReferrer = (
+      owner: { user: Keypair; phone: number[] },
+      referrer: { phone: number[] }
+    ) =>
+      program.methods
+        .setReferrer()
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          referrerAccount: userPda(referrer.phone),
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+    const complete = async (
+      owner: { phone: number[] },
+      txId: number[],
+      referrer: { phone: number[] } | null
+    ) =>
+      program.methods
+        .completeTransaction()
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          role: null,
+          referrerAccount: referrer ? userPda(referrer.phone) : null,
+          dailyStats: dailyStatsPda(await chainDay()),
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    after(() => setShare(0));
+
+    it("credits the referrer a share of each fee", async () => {
+      await setShare(5_000);
+      const referrer = await registerUser();
+      const owner = await registerUser();
+      await setReferrer(owner, referrer);
+
+      for (let i = 0; i < 2; i++) {
+        const txId = await createTransaction(owner, { amountNgn: 1_000 });
+        await complete(owner, txId, referrer);
+      }
+
+      const account = await program.account.userAccount.fetch(
+        userPda(referrer.phone)
+      );
+      expect(account.referralRewards.toNumber()).to.equal(10);
+    });
+
+    it("rejects a referrer the user was not referred by", async () => {
+      await setShare(5_000);
+      const referrer = await registerUser();
+      const other = await registerUser();
+      const owner = await registerUser();
+      await setReferrer(owner, referrer);
+      const txId = await createTransaction(owner);
+
+      await expectError(complete(owner, txId, other), "InvalidReferrer");
+    });
+
+    it("requires the referrer account for a referred user", async () => {
+      const referrer = await registerUser();
+      const owner = await registerUser();
+      await setReferrer(owner, referrer);
+      const txId = await createTransaction(owner);
+
+      await expectError(complete(owner, txId, null), "ReferrerAccountRequired");
+    });
+
+    it("shares the fee of a force-completed transaction", async () => {
+      await setShare(5_000);
+      const referrer = await registerUser();
+      const owner = await registerUser();
+      await setReferrer(owner, referrer);
+      const txId = await createTransaction(owner, { amountNgn: 1_000 });
+
+      await program.methods
+        .forceComplete(0)
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          referrerAccount: userPda(referrer.phone),
+          dailyStats: dailyStatsPda(await chainDay()),
+          admin: admin.publicKey,
+        })
+        .rpc();
+      const account = await program.account.userAccount.fetch(
+        userPda(referrer.phone)
+      );
+      expect(account.referralRewards.toNumber()).to.equal(5);
+    });
+
+    it("lets a closed referrer's share lapse", async () => {
+      await setShare(5_000);
+      const referrer = await registerUser();
+      const owner = await registerUser();
+      await setReferrer(owner, referrer);
+      await program.methods
+        .closeUser()
+        .accountsPartial({
+          userAccount: userPda(referrer.phone),
+          config: configPda,
+          user: referrer.user.publicKey,
+        })
+        .signers([referrer.user])
+        .rpc();
+
+      const txId = await createTransaction(owner);
+      await complete(owner, txId, referrer);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ completed: {} });
+    });
+
+    it("sets the referrer only once", async () => {
+      const referrer = await registerUser();
+      const owner = await registerUser();
+      await setReferrer(owner, referrer);
+
+      await expectError(setReferrer(owner, owner), "ReferrerAlreadySet");
+    });
+  });
+
+  describe("unique wallets", () => {
+    const setUnique = (on: boolean) =>
+      program.methods
+        .setUniqueWallets(on)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const register = (user: Keypair, phone: number[]) =>
+      program.methods
+        .registerUser(phone, Array(32).fill(7))
+        .accountsPartial({
+          userAccount: userPda(phone),
+          walletIndex: walletPda(user.publicKey),
+          config: configPda,
+          user: user.publicKey,
+        })
+        .signers([user])
+        .rpc();
+
+    after(() => setUnique(false));
+
+    it("lets a wallet hold only one phone", async () => {
+      await setUnique(true);
+      const user = await fundedKeypair();
+      const phone = nextPhone();
+      await register(user, phone);
+
+      const index = await program.account.walletIndex.fetch(
+        walletPda(user.publicKey)
+      );
+      expect(index.phoneNumber).to.deep.equal(phone);
+      try {
+        await register(user, nextPhone());
+        expect.fail("second phone registered");
+      } catch (err) {
+        expect(String(err)).to.include("already in use");
+      }
+    });
+
+    it("frees the wallet when the user closes", async () => {
+      await setUnique(true);
+      const user = await fundedKeypair();
+      const phone = nextPhone();
+      await register(user, phone);
+      await program.methods
+        .closeUser()
+        .accountsPartial({
+          userAccount: userPda(phone),
+          walletIndex: walletPda(user.publicKey),
+          config: configPda,
+          user: user.publicKey,
+        })
+        .signers([user])
+        .rpc();
+
+      await register(user, nextPhone());
+    });
+
+    it("requires the index while enabled", async () => {
+      await setUnique(true);
+      await expectError(registerUser(), "WalletIndexRequired");
+    });
+  });
+
+  describe("daily stats", () => {
+    const getDailyStats = (day: number) =>
+      program.methods
+        .getDailyStats(day)
+        .accountsPartial({ dailyStats: dailyStatsPda(day) })
+        .view();
+
+    it("rolls completed volume up by UTC day", async () => {
+      const owner = await registerUser();
+      const first = await createTransaction(owner, { amountNgn: 1_000 });
+      const second = await createTransaction(owner, { amountNgn: 2_500 });
+      await completeTransaction(owner, first);
+      const day = await chainDay();
+      const before = await program.account.dailyStats.fetch(
+        dailyStatsPda(day)
+      );
+      await completeTransaction(owner, second);
+
+      const stats = await getDailyStats(day);
+      expect(stats.day).to.equal(day);
+      expect(stats.totalVolume.sub(before.totalVolume).toNumber()).to.equal(
+        2_500
+      );
+      expect(stats.txCount.sub(before.txCount).toNumber()).to.equal(1);
+    });
+
+    it("counts force-completed volume too", async () => {
+      const owner = await registerUser();
+      const first = await createTransaction(owner, { amountNgn: 1_000 });
+      const second = await createTransaction(owner, { amountNgn: 4_000 });
+      await completeTransaction(owner, first);
+      const day = await chainDay();
+      const before = await program.account.dailyStats.fetch(
+        dailyStatsPda(day)
+      );
+      await forceComplete(owner, second);
+
+      const stats = await getDailyStats(day);
+      expect(stats.totalVolume.sub(before.totalVolume).toNumber()).to.equal(
+        4_000
+      );
+      expect(stats.txCount.sub(before.txCount).toNumber()).to.equal(1);
+    });
+  });
+
+  describe("loyalty tiers", () => {
+    const setTiers = (thresholds: number[], stepBps = 0) =>
+      program.methods
+        .setLoyaltyTiers(
+          thresholds.map((threshold) => new BN(threshold)),
+          stepBps
+        )
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const levelOf = async (owner: { phone: number[] }) =>
+      (await program.account.userAccount.fetch(userPda(owner.phone)))
+        .loyaltyLevel;
+
+    after(() => setTiers([0, 0, 0]));
+
+    it("upgrades the user as volume crosses each threshold", async () => {
+      await setTiers([1_000, 3_000, 6_000]);
+      const owner = await registerUser();
+
+      for (const [amountNgn, level] of [
+        [1_000, 1],
+        [2_000, 2],
+        [3_000, 3],
+      ]) {
+        const txId = await createTransaction(owner, { amountNgn });
+        await completeTransaction(owner, txId);
+        expect(await levelOf(owner)).to.equal(level);
+      }
+    });
+
+    it("does not downgrade when thresholds rise", async () => {
+      await setTiers([1_000, 3_000, 6_000]);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 1_000 });
+      await completeTransaction(owner, txId);
+
+      await setTiers([5_000, 10_000, 20_000]);
+      const next = await createTransaction(owner, { amountNgn: 1_000 });
+      await completeTransaction(owner, next);
+      expect(await levelOf(owner)).to.equal(1);
+    });
+
+    it("rejects thresholds that are not ascending", async () => {
+      await expectError(setTiers([3_000, 1_000, 0]), "InvalidConfig");
+      await expectError(setTiers([0, 1_000, 0]), "InvalidConfig");
+    });
+  });
+
+  describe("hold queue", () => {
+    const review = (
+      method: "holdTransaction" | "releaseTransaction" | "rejectHeld",
+      owner: { phone: number[] },
+      txId: number[]
+    ) =>
+      program.methods[method]()
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+    const statusOf = async (txId: number[]) =>
+      (await program.account.transactionAccount.fetch(txPda(txId))).status;
+
+    it("blocks completion until the transaction is released", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await review("holdTransaction", owner, txId);
+      expect(await statusOf(txId)).to.deep.equal({ held: {} });
+
+      await expectError(
+        completeTransaction(owner, txId),
+        "InvalidTransactionStatus"
+      );
+      await review("releaseTransaction", owner, txId);
+      await completeTransaction(owner, txId);
+      expect(await statusOf(txId)).to.deep.equal({ completed: {} });
+    });
+
+    it("fails a rejected transaction", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await review("holdTransaction", owner, txId);
+
+      await review("rejectHeld", owner, txId);
+      expect(await statusOf(txId)).to.deep.equal({ failed: {} });
+      await expectError(
+        review("releaseTransaction", owner, txId),
+        "InvalidTransactionStatus"
+      );
+    });
+  });
+
+  describe("batch compute guard", () => {
+    it("reverts cleanly when the budget can't cover the batch", async () => {
+      const owner = await registerUser();
+      const account = {
+        pubkey: userPda(owner.phone),
+        isSigner: false,
+        isWritable: false,
+      };
+
+      await expectError(
+        program.methods
+          .getBalancesBatch()
+          .remainingAccounts(Array(32).fill(account))
+          .preInstructions([
+            ComputeBudgetProgram.setComputeUnitLimit({ units: 15_000 }),
+          ])
+          .rpc(),
+        "InsufficientCompute"
+      );
+    });
+  });
+
+  describe("create_transaction_with_quote", () => {
+    const pricing = Keypair.generate();
+    const RATE = 1_600;
+
+    const setPricingKey = (key: PublicKey) =>
+      program.methods
+        .setPricingKey(key)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    // With a risk signer, its check goes last, right before the instruction
+    const createWithQuote = async (
+      owner: { user: Keypair; phone: number[] },
+      amountNgn: number,
+      expiresAt: number,
+      risk?: { nonce: number[]; signer: Keypair }
+    ) => {
+      const txId = newTxId();
+      const quote = { rate: new BN(RATE), expiresAt: new BN(expiresAt) };
+      const verify = Ed25519Program.createInstructionWithPrivateKey({
+        privateKey: pricing.secretKey,
+        message: Buffer.concat([
+          quote.rate.toArrayLike(Buffer, "le", 8),
+          quote.expiresAt.toArrayLike(Buffer, "le", 8),
+        ]),
+      });
+      const riskVerify = risk
+        ? Ed25519Program.createInstructionWithPrivateKey({
+            privateKey: risk.signer.secretKey,
+            message: Buffer.concat([
+              Buffer.from(txId),
+              Buffer.from(risk.nonce),
+            ]),
+          })
+        : null;
+      await program.methods
+        .createTransactionWithQuote(
+          txId,
+          owner.phone,
+          new BN(amountNgn),
+          quote,
+          Array.from(verify.data.subarray(48, 112)),
+          risk?.nonce ?? null,
+          riskVerify ? Array.from(riskVerify.data.subarray(48, 112)) : null
+        )
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          parentTransaction: null,
+          externalRef: null,
+          reservation: reservationPda(txId),
+          userAccount: userPda(owner.phone),
+          userBudget: null,
+          config: configPda,
+          user: owner.user.publicKey,
+          guardian: null,
+          instructions: SYSVAR_INSTRUCTIONS_PUBKEY,
+        })
+        .preInstructions([
+          await reserveTxId(owner.user, txId).instruction(),
+          verify,
+          ...(riskVerify ? [riskVerify] : []),
+        ])
+        .signers([owner.user])
+        .rpc();
+      return txId;
+    };
+
+    before(() => setPricingKey(pricing.publicKey));
+    after(() => setPricingKey(PublicKey.default));
+
+    it("locks in the quoted rate", async () => {
+      const owner = await registerUser();
+      const expiresAt = Math.floor(Date.now() / 1000) + 600;
+      const txId = await createWithQuote(owner, 8_000, expiresAt);
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.txType).to.deep.equal({ crypto: {} });
+      expect(tx.quotedRate.toNumber()).to.equal(RATE);
+      expect(tx.amountUsdc.toNumber()).to.equal(5_000_000);
+    });
+
+    it("rejects an expired quote", async () => {
+      const owner = await registerUser();
+      const expiresAt = Math.floor(Date.now() / 1000) - 600;
+      await expectError(
+        createWithQuote(owner, 8_000, expiresAt),
+        "QuoteExpired"
+      );
+    });
+
+    it("works alongside risk checks", async () => {
+      const riskEngine = Keypair.generate();
+      const nonce = Array(16).fill(4);
+      const setRiskChecks = async (enabled: boolean) => {
+        await program.methods
+          .setRiskApprover(
+            enabled ? riskEngine.publicKey : PublicKey.default,
+            new BN(0)
+          )
+          .accountsPartial({ config: configPda, admin: admin.publicKey })
+          .rpc();
+        await program.methods
+          .setRiskChecksEnabled(enabled)
+          .accountsPartial({ config: configPda, admin: admin.publicKey })
+          .rpc();
+      };
+      const owner = await registerUser();
+      const expiresAt = Math.floor(Date.now() / 1000) + 600;
+
+      await setRiskChecks(true);
+      try {
+        await expectError(
+          createWithQuote(owner, 8_000, expiresAt),
+          "InvalidSignature"
+        );
+        const txId = await createWithQuote(owner, 8_000, expiresAt, {
+          nonce,
+          signer: riskEngine,
+        });
+        const tx = await program.account.transactionAccount.fetch(
+          txPda(txId)
+        );
+        expect(tx.quotedRate.toNumber()).to.equal(RATE);
+        expect(tx.riskNonce).to.deep.equal(nonce);
+      } finally {
+        await setRiskChecks(false);
+      }
+    });
+  });
+
+  describe("update_config_atomic", () => {
+    type ConfigParams = Parameters<
+      typeof program.methods.updateConfigAtomic
+    >[0];
+    const updateAtomic = async (params: ConfigParams) =>
+      program.methods
+        .updateConfigAtomic(params)
+        .accountsPartial({
+          config: configPda,
+          configSnapshot: await nextConfigSnapshot(),
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+    const currentParams = async (): Promise<ConfigParams> => {
+      const config = await program.account.config.fetch(configPda);
+      return {
+        cryptoFeeBps: config.cryptoFeeBps,
+        airtimeFeeBps: config.airtimeFeeBps,
+        minLimit: config.minLimit,
+        maxLimit: config.maxLimit,
+        minFeeNgn: config.minFeeNgn,
+        maxFeeNgn: config.maxFeeNgn,
+        failureRebateBps: config.failureRebateBps,
+        referrerFeeShareBps: config.referrerFeeShareBps,
+        limitMultiplierBps: config.limitMultiplierBps,
+      };
+    };
+
+    let initial: ConfigParams;
+    before(async () => {
+      initial = await currentParams();
+    });
+    after(() => updateAtomic(initial));
+
+    it("applies every field in one update", async () => {
+      const original = await currentParams();
+      await updateAtomic({
+        ...original,
+        minFeeNgn: new BN(5),
+        maxFeeNgn: new BN(500),
+        failureRebateBps: 2_500,
+      });
+
+      const config = await program.account.config.fetch(configPda);
+      expect(config.minFeeNgn.toNumber()).to.equal(5);
+      expect(config.maxFeeNgn.toNumber()).to.equal(500);
+      expect(config.failureRebateBps).to.equal(2_500);
+    });
+
+    it("rejects the whole update if any invariant fails", async () => {
+      const original = await currentParams();
+      await expectError(
+        updateAtomic({
+          ...original,
+          failureRebateBps: 2_500,
+          minFeeNgn: new BN(600),
+          maxFeeNgn: new BN(500),
+        }),
+        "InvalidConfig"
+      );
+
+      const config = await program.account.config.fetch(configPda);
+      expect(config.failureRebateBps).to.equal(original.failureRebateBps);
+    });
+
+    it("is admin only, even for a fee role", async () => {
+      const CAN_UPDATE_FEES = 1 << 1;
+      const operator = await fundedKeypair();
+      await program.methods
+        .grantRole(operator.publicKey, CAN_UPDATE_FEES)
+        .accountsPartial({
+          role: rolePda(operator.publicKey),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+      await expectError(
+        program.methods
+          .updateConfigAtomic(await currentParams())
+          .accountsPartial({
+            config: configPda,
+            configSnapshot: await nextConfigSnapshot(),
+            role: rolePda(operator.publicKey),
+            authority: operator.publicKey,
+          })
+          .signers([operator])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("social recovery", () => {
+    const recoveryPda = (phone: number[]) =>
+      PublicKey.findProgramAddressSync(
+        [Buffer.from("recovery"), Buffer.from(phone)],
+        program.programId
+      )[0];
+    const setGuardians = (
+      owner: { user: Keypair; phone: number[] },
+      guardians: PublicKey[],
+      threshold: number
+    ) =>
+      program.methods
+        .setRecoveryGuardians(
+          [
+            ...guardians,
+            ...Array(5 - guardians.length).fill(PublicKey.default),
+          ],
+          threshold
+        )
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          user: owner.user.publicKey,
+        })
+        .signers([owner.user])
+        .rpc();
+    const approve = (
+      owner: { phone: number[] },
+      guardian: Keypair,
+      newWallet: PublicKey
+    ) =>
+      program.methods
+        .approveRecovery(newWallet)
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          recoveryRequest: recoveryPda(owner.phone),
+          guardian: guardian.publicKey,
+        })
+        .signers([guardian])
+        .rpc();
+    const recover = (
+      owner: { user: Keypair; phone: number[] },
+      newWallet: Keypair,
+      indexed = false
+    ) =>
+      program.methods
+        .recoverViaGuardians(newWallet.publicKey)
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          recoveryRequest: recoveryPda(owner.phone),
+          oldWalletIndex: walletPda(owner.user.publicKey),
+          newWalletIndex: indexed ? walletPda(newWallet.publicKey) : null,
+          config: configPda,
+          newWallet: newWallet.publicKey,
+        })
+        .signers([newWallet])
+        .rpc();
+
+    it("rotates the wallet once the threshold approves", async () => {
+      const owner = await registerUser();
+      const guardians = await Promise.all(
+        Array.from({ length: 3 }, () => fundedKeypair())
+      );
+      const newWallet = await fundedKeypair();
+      await setGuardians(
+        owner,
+        guardians.map((guardian) => guardian.publicKey),
+        2
+      );
+
+      await approve(owner, guardians[0], newWallet.publicKey);
+      await expectError(
+        recover(owner, newWallet),
+        "RecoveryThresholdNotMet"
+      );
+      await approve(owner, guardians[2], newWallet.publicKey);
+      await recover(owner, newWallet);
+
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.wallet.equals(newWallet.publicKey)).to.be.true;
+    });
+
+    it("moves the wallet index to the new wallet", async () => {
+      const setUnique = (on: boolean) =>
+        program.methods
+          .setUniqueWallets(on)
+          .accountsPartial({ config: configPda, admin: admin.publicKey })
+          .rpc();
+      const register = (user: Keypair, phone: number[]) =>
+        program.methods
+          .registerUser(phone, Array(32).fill(7))
+          .accountsPartial({
+            userAccount: userPda(phone),
+            walletIndex: walletPda(user.publicKey),
+            config: configPda,
+            user: user.publicKey,
+          })
+          .signers([user])
+          .rpc();
+
+      await setUnique(true);
+      try {
+        const owner = { user: await fundedKeypair(), phone: nextPhone() };
+        await register(owner.user, owner.phone);
+        const guardian = await fundedKeypair();
+        const newWallet = await fundedKeypair();
+        await setGuardians(owner, [guardian.publicKey], 1);
+        await approve(owner, guardian, newWallet.publicKey);
+        await expectError(recover(owner, newWallet), "WalletIndexRequired");
+        await recover(owner, newWallet, true);
+
+        const index = await program.account.walletIndex.fetch(
+          walletPda(newWallet.publicKey)
+        );
+        expect(index.phoneNumber).to.deep.equal(owner.phone);
+        const oldIndex = await provider.connection.getAccountInfo(
+          walletPda(owner.user.publicKey)
+        );
+        expect(oldIndex).to.equal(null);
+        // The lost wallet is free to register again
+        await register(owner.user, nextPhone());
+      } finally {
+        await setUnique(false);
+      }
+    });
+
+    it("only counts listed guardians", async () => {
+      const owner = await registerUser();
+      const guardian = await fundedKeypair();
+      const stranger = await fundedKeypair();
+      await setGuardians(owner, [guardian.publicKey], 1);
+
+      await expectError(
+        approve(owner, stranger, stranger.publicKey),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects a threshold above the guardian count", async () => {
+      const owner = await registerUser();
+      const guardian = await fundedKeypair();
+      await expectError(
+        setGuardians(owner, [guardian.publicKey], 2),
+        "InvalidGuardians"
+      );
+    });
+  });
+
+  describe("reconcile_user", () => {
+    const reconcile = (owner: { phone: number[] }, txIds: number[][]) =>
+      program.methods
+        .reconcileUser()
+        .accountsPartial({
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .remainingAccounts(
+          txIds.map((txId) => ({
+            pubkey: txPda(txId),
+            isSigner: false,
+            isWritable: false,
+          }))
+        )
+        .rpc();
+
+    it("rebuilds volume from the user's transactions", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      const settled = await createTransaction(owner, { amountNgn: 2_000 });
+      await completeTransaction(owner, settled);
+      const pending = await createTransaction(owner, { amountNgn: 1_000 });
+      // Drift the counter away from the transaction set
+      await program.methods
+        .transferVolume(new BN(500))
+        .accountsPartial({
+          fromUser: userPda(owner.phone),
+          toUser: userPda(other.phone),
+          config: configPda,
+          admin: admin.publicKey,
+        })
+        .rpc();
+
+      await reconcile(owner, [settled, pending]);
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalVolume.toNumber()).to.equal(2_000);
+    });
+
+    it("rejects another user's transactions", async () => {
+      const owner = await registerUser();
+      const other = await registerUser();
+      const txId = await createTransaction(other);
+      await expectError(reconcile(owner, [txId]), "Unauthorized");
+    });
+
+    it("rejects a transaction passed twice", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await expectError(reconcile(owner, [txId, txId]), "DuplicateAccount");
+    });
+
+    it("counts each transaction where settlement credited it", async () => {
+      const setMinVolumeAccrual = (amount: number) =>
+        program.methods
+          .setMinVolumeAccrual(new BN(amount))
+          .accountsPartial({ config: configPda, admin: admin.publicKey })
+          .rpc();
+      const owner = await registerUser();
+      const micro = await createTransaction(owner, { amountNgn: 300 });
+      await setMinVolumeAccrual(500);
+      try {
+        await completeTransaction(owner, micro);
+      } finally {
+        await setMinVolumeAccrual(0);
+      }
+
+      await reconcile(owner, [micro]);
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.microVolume.toNumber()).to.equal(300);
+      expect(user.totalVolume.toNumber()).to.equal(0);
+    });
+  });
+
+  describe("max fee ratio", () => {
+    const setMaxFeeRatio = (bps: number) =>
+      program.methods
+        .setMaxFeeRatioBps(bps)
+        .accountsPartial({
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    after(() => setMaxFeeRatio(0));
+
+    it("accepts a fee exactly at the ratio", async () => {
+      // Airtime is 100 bps, so 1_000 NGN carries a fee of 10
+      await setMaxFeeRatio(100);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 1_000 });
+
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.fee.toNumber()).to.equal(10);
+    });
+
+    it("rejects a fee just above the ratio", async () => {
+      await setMaxFeeRatio(99);
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { amountNgn: 1_000 }),
+        "FeeTooHigh"
+      );
+    });
+  });
+
+  describe("refund_transaction", () => {
+    const refund = (
+      owner: { phone: number[] },
+      txId: number[],
+      amount: number
+    ) =>
+      program.methods
+        .refundTransaction(new BN(amount))
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    it("accumulates partial refunds up to the full amount", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 3_000 });
+      await completeTransaction(owner, txId);
+
+      await refund(owner, txId, 1_000);
+      await refund(owner, txId, 500);
+      let tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.refundedAmount.toNumber()).to.equal(1_500);
+      expect(tx.status).to.deep.equal({ completed: {} });
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.totalVolume.toNumber()).to.equal(1_500);
+
+      await refund(owner, txId, 1_500);
+      tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ refunded: {} });
+    });
+
+    it("rejects refunds beyond the original amount", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 3_000 });
+      await completeTransaction(owner, txId);
+      await refund(owner, txId, 2_000);
+
+      await expectError(refund(owner, txId, 1_001), "InvalidRefundAmount");
+    });
+
+    it("reverses the counter settlement credited", async () => {
+      const setMinVolumeAccrual = (amount: number) =>
+        program.methods
+          .setMinVolumeAccrual(new BN(amount))
+          .accountsPartial({ config: configPda, admin: admin.publicKey })
+          .rpc();
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn: 300 });
+
+      await setMinVolumeAccrual(500);
+      try {
+        await completeTransaction(owner, txId);
+      } finally {
+        await setMinVolumeAccrual(0);
+      }
+      await refund(owner, txId, 100);
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      expect(user.microVolume.toNumber()).to.equal(200);
+      expect(user.totalVolume.toNumber()).to.equal(0);
+    });
+
+    it("takes the USDC leg off in proportion", async () => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, {
+        txType: { crypto: {} },
+        amountNgn: 3_000,
+        amountUsdc: 2_000_000,
+      });
+      await completeTransaction(owner, txId);
+      const usdcVolume = async () =>
+        (
+          await program.account.userAccount.fetch(userPda(owner.phone))
+        ).totalUsdcVolume.toNumber();
+
+      await refund(owner, txId, 1_000);
+      expect(await usdcVolume()).to.equal(1_333_334);
+      await refund(owner, txId, 2_000);
+      expect(await usdcVolume()).to.equal(0);
+    });
+  });
+
+  describe("min settlement delay", () => {
+    const setDelay = (seconds: number) =>
+      program.methods
+        .setMinSettlementDelay(new BN(seconds))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    after(() => setDelay(0));
+
+    it("rejects completion before the delay has passed", async () => {
+      await setDelay(3_600);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+
+      await expectError(
+        completeTransaction(owner, txId),
+        "SettlementTooSoon"
+      );
+    });
+
+    it("lets force_complete bypass the delay", async () => {
+      await setDelay(3_600);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+
+      await forceComplete(owner, txId);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ completed: {} });
+    });
+  });
+
+  describe("transaction hash chain", () => {
+    const chainHashOf = async (owner: { phone: number[] }) =>
+      Buffer.from(
+        (
+          await program.methods
+            .getUserStats()
+            .accountsPartial({ userAccount: userPda(owner.phone) })
+            .view()
+        ).txChainHash
+      );
+
+    it("folds each completion into the user's hash", async () => {
+      const owner = await registerUser();
+      expect(await chainHashOf(owner)).to.deep.equal(Buffer.alloc(32));
+
+      let expected = Buffer.alloc(32);
+      for (const amountNgn of [1_000, 2_500]) {
+        const txId = await createTransaction(owner, { amountNgn });
+        await completeTransaction(owner, txId);
+        const tx = await program.account.transactionAccount.fetch(txPda(txId));
+        expected = Buffer.from(
+          keccak_256(
+            Buffer.concat([
+              expected,
+              Buffer.from(txId),
+              tx.amountNgn.toArrayLike(Buffer, "le", 8),
+              tx.timestamp.toArrayLike(Buffer, "le", 8),
+            ])
+          )
+        );
+        expect(await chainHashOf(owner)).to.deep.equal(expected);
+      }
+    });
+  });
+
+  describe("net volume accrual", () => {
+    const setAccrueNet = (on: boolean) =>
+      program.methods
+        .setAccrueNet(on)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const settle = async (amountNgn: number) => {
+      const owner = await registerUser();
+      const txId = await createTransaction(owner, { amountNgn });
+      await completeTransaction(owner, txId);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      const user = await program.account.userAccount.fetch(
+        userPda(owner.phone)
+      );
+      return { tx, volume: user.totalVolume.toNumber() };
+    };
+
+    after(() => setAccrueNet(false));
+
+    it("accrues the gross amount by default", async () => {
+      await setAccrueNet(false);
+      const { tx, volume } = await settle(1_234);
+
+      expect(volume).to.equal(1_234);
+      expect(tx.accruedVolume.toNumber()).to.equal(1_234);
+    });
+
+    it("accrues net of the fee with nothing lost", async () => {
+      await setAccrueNet(true);
+      const { tx, volume } = await settle(1_234);
+
+      expect(tx.fee.toNumber()).to.be.greaterThan(0);
+      expect(volume + tx.fee.toNumber()).to.equal(1_234);
+      expect(tx.accruedVolume.toNumber()).to.equal(volume);
+    });
+  });
+
+  describe("max amount digits", () => {
+    const setMaxDigits = (digits: number) =>
+      program.methods
+        .setMaxAmountDigits(digits)
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+
+    after(() => setMaxDigits(0));
+
+    it("accepts amounts at the digit limit", async () => {
+      await setMaxDigits(2);
+      const owner = await registerUser();
+      await createTransaction(owner, { amountNgn: 1_500 });
+      await createTransaction(owner, { amountNgn: 250_000 });
+    });
+
+    it("rejects amounts with one digit too many", async () => {
+      await setMaxDigits(2);
+      const owner = await registerUser();
+      await expectError(
+        createTransaction(owner, { amountNgn: 1_550 }),
+        "AmountTooPrecise"
+      );
+    });
+  });
+
+  describe("fail grace period", () => {
+    const setGracePeriod = (seconds: number) =>
+      program.methods
+        .setFailGracePeriod(new BN(seconds))
+        .accountsPartial({ config: configPda, admin: admin.publicKey })
+        .rpc();
+    const fail = (owner: { phone: number[] }, txId: number[]) =>
+      program.methods
+        .failTransaction()
+        .accountsPartial({
+          transactionAccount: txPda(txId),
+          userAccount: userPda(owner.phone),
+          config: configPda,
+          role: null,
+          authority: admin.publicKey,
+        })
+        .rpc();
+
+    after(() => setGracePeriod(0));
+
+    it("completes a failed transaction within the grace window", async () => {
+      await setGracePeriod(600);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await fail(owner, txId);
+
+      await completeTransaction(owner, txId);
+      const tx = await program.account.transactionAccount.fetch(txPda(txId));
+      expect(tx.status).to.deep.equal({ completed: {} });
+      expect(tx.retryCount).to.equal(0);
+    });
+
+    it("leaves failures terminal without a grace window", async () => {
+      await setGracePeriod(0);
+      const owner = await registerUser();
+      const txId = await createTransaction(owner);
+      await fail(owner, txId);
+
+      await expectError(
+        completeTransaction(owner, txId),
+        "InvalidTransactionStatus"
+      );
+    });
+
+    it("re-holds the exact amount and takes back the rebate", async () => {
+      const setConfig = async (
+        bucketSize: number,
+        rebateBps: number,
+        enforced: boolean
+      ) => {
+        const accounts = { config: configPda, admin: admin.publicKey };
+        await program.methods
+          .setBucketSize(new BN(bucketSize))
+          .accountsPartial(accounts)
+          .rpc();
+        await program.methods
+          .setFailureRebateBps(rebateBps)
+          .accountsPartial({
+            config: configPda,
+            role: null,
+            authority: admin.publicKey,
+          })
+          .rpc();
+        await program.methods
+          .setBalanceEnforced(enforced)
+          .accountsPartial(accounts)
+          .rpc();
+      };
+      await setGracePeriod(600);
+      await setConfig(500, 5_000, true);
+      try {
+        const owner = await registerUser();
+        await program.methods
+          .setBucketed(true)
+          .accountsPartial({
+            userAccount: userPda(owner.phone),
+            user: owner.user.publicKey,
+          })
+          .signers([owner.user])
+          .rpc();
+        await program.methods
+          .creditBalance(new BN(2_000))
+          .accountsPartial({
+            userAccount: userPda(owner.phone),
+            config: configPda,
+            admin: admin.publicKey,
+          })
+          .rpc();
+        const balance = async () =>
+          (
+            await program.account.userAccount.fetch(userPda(owner.phone))
+          ).availableBalance.toNumber();
+
+        // Stored as 1,000; the 12 fee and the hold use the exact 1,234
+        const txId = await createTransaction(owner, { amountNgn: 1_234 });
+        await fail(owner, txId);
+        expect(await balance()).to.equal(2_000 - 12 + 6);
+
+        await completeTransaction(owner, txId);
+        const tx = await program.account.transactionAccount.fetch(
+          txPda(txId)
+        );
+        expect(tx.amountNgn.toNumber()).to.equal(1_000);
+        expect(tx.heldAmount.toNumber()).to.equal(1_234);
+        expect(tx.debitedAmount.toNumber()).to.equal(1_234 + 12);
+        expect(tx.feeRebate.toNumber()).to.equal(0);
+        expect(await balance()).to.equal(2_000 - 1_234 - 12);
+      } finally {
+        await setConfig(0, 0, false);
+      }
+    });
   });
 });